@@ -0,0 +1,31 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dna_sequence_analysis::{
+    aliner::{DiffStat, Score},
+    bench_support::{mutate, random_dna},
+};
+
+/// Global, semiglobal and banded global alignment of a sequence against a copy with 1%
+/// substitutions
+fn alignment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("alignment");
+    group.sample_size(10);
+    for len in [1_000, 10_000] {
+        let reference = random_dna(len, 7);
+        let query = mutate(&reference, 100);
+        let diffstat = || DiffStat::new(&reference[..], &query[..], (-5, -1), Score::new(1, -1));
+
+        group.bench_with_input(BenchmarkId::new("global", len), &len, |b, _| {
+            b.iter(|| diffstat().pairwise_aligner_global())
+        });
+        group.bench_with_input(BenchmarkId::new("semiglobal", len), &len, |b, _| {
+            b.iter(|| diffstat().pairwise_aligner_semiglobal())
+        });
+        group.bench_with_input(BenchmarkId::new("banded_global", len), &len, |b, _| {
+            b.iter(|| diffstat().pairwise_aligner_banded_global(8, 20))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, alignment);
+criterion_main!(benches);
@@ -1,19 +1,21 @@
 use dna_sequence_analysis::{
     aliner::{DiffStat, GapPanelty, Score},
+    batch::all_pairs_mutation_matrix,
     init_logging,
     mutation_detection::Muatation,
-    reader::FastaReader,
+    reader::{read_directory, FastaReader},
 };
 use std::time::Instant;
+use tabled::builder::Builder;
 
 fn main() -> anyhow::Result<()> {
     let _guard = init_logging();
     let covid_beta = FastaReader::from_file("./assets/SARS-beta.fasta")?;
     let covid_delta = FastaReader::from_file("./assets/SARS-delta.fasta")?;
 
-    let beta_record = covid_beta.records().next().unwrap()?;
+    let beta_record = covid_beta.single_record()?;
     let beta_seq = beta_record.seq();
-    let delta_record = covid_delta.records().next().unwrap()?;
+    let delta_record = covid_delta.single_record()?;
     let delta_seq = delta_record.seq();
 
     let score = Score::new(1, -1);
@@ -29,5 +31,23 @@ fn main() -> anyhow::Result<()> {
 
     println!("Score: {}", ms.mutation_score().unwrap());
     println!("time taken: {:?}", time.elapsed());
+
+    // Compare all sequences with one another
+    let time = Instant::now();
+    let variants = read_directory("./assets")?;
+    let matrix = all_pairs_mutation_matrix(&variants, gap, score);
+
+    let mut builder = Builder::default();
+    builder.set_header(
+        std::iter::once("miss_match".to_string()).chain(variants.iter().map(|(id, _)| id.clone())),
+    );
+    for ((id, _), row) in variants.iter().zip(&matrix) {
+        builder.push_record(
+            std::iter::once(id.clone())
+                .chain(row.iter().map(|stats| stats.miss_match().to_string())),
+        );
+    }
+    println!("{}", builder.build());
+    println!("time taken: {:?}", time.elapsed());
     Ok(())
 }
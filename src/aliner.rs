@@ -1,19 +1,51 @@
 //! This module is used to align two or more DNA/RNA sequences
 //! to "align" them, see: https://en.wikipedia.org/wiki/Sequence_alignment
 
-use std::fmt::Display;
+use std::{cmp::Ordering, collections::HashMap, fmt::Display, ops::Range};
 
 use bio::alignment::{
     distance::{hamming, levenshtein},
     pairwise::{MatchFunc, Scoring},
-    Alignment,
+    Alignment, AlignmentMode, AlignmentOperation,
+};
+use tabled::{Table, Tabled};
+use terminal_size::Width;
+
+use crate::{
+    mutation_detection::{is_transition, is_transversion},
+    sequence::{collapse_homopolymers, expand_position, iupac_expansion, Alphabet},
+    similarity::DistanceMetric,
 };
 
 type PairwiseAlignment = bio::alignment::Alignment;
 type PartialorderAlignment = bio::alignment::poa::Alignment;
 
+/// Number of alignment columns in a window of [DiffStat::core_alignment]
+const CORE_WINDOW: usize = 10;
+
+/// Columns of [DiffStat::pretty_print_auto] when not printing to a terminal
+const DEFAULT_PRETTY_COLUMNS: usize = 120;
+
+/// Fewest columns of [DiffStat::pretty_print_auto], however narrow the terminal
+const MIN_PRETTY_COLUMNS: usize = 20;
+
+/// Query to reference length ratio above which [DiffStat::warn_if_suspicious] warns
+const SUSPICIOUS_LENGTH_RATIO: usize = 10;
+
+/// Columns of [DiffStat::pretty_print_auto] for a terminal `width`, if printing to one
+fn pretty_columns(width: Option<usize>) -> usize {
+    width.map_or(DEFAULT_PRETTY_COLUMNS, |width| {
+        width.max(MIN_PRETTY_COLUMNS)
+    })
+}
+
+/// [pretty_columns] of the terminal being printed to
+fn terminal_pretty_columns() -> usize {
+    pretty_columns(terminal_size::terminal_size().map(|(Width(width), _)| width as usize))
+}
+
 ///  Scoring rule for [Substitution matrix](https://en.wikipedia.org/wiki/Smith_Waterman_algorithm#Substitution_matrix)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Score {
     r#match: i32,
     miss_match: i32,
@@ -68,9 +100,151 @@ impl MatchFunc for Score {
     }
 }
 
+/// Scoring aware of IUPAC ambiguity codes (see [iupac_expansion]): identical bases score a
+/// match, differing ones that may stand for the same base (e.g. `A` and `R` or `N`) a partial
+/// match, anything else a mismatch. Case insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IupacScore {
+    r#match: i32,
+    partial: i32,
+    miss_match: i32,
+}
+
+impl Display for IupacScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "IupacScore(match={},partial={},miss-match={})",
+            self.r#match, self.partial, self.miss_match
+        )
+    }
+}
+
+impl IupacScore {
+    pub fn new(r#match: i32, partial: i32, miss_match: i32) -> Self {
+        tracing::info!(
+            "Generating IupacScore match={} partial={} miss-match={}",
+            r#match,
+            partial,
+            miss_match
+        );
+        Self {
+            r#match,
+            partial,
+            miss_match,
+        }
+    }
+}
+
+impl MatchFunc for IupacScore {
+    fn score(&self, a: u8, b: u8) -> i32 {
+        if a.eq_ignore_ascii_case(&b) {
+            return self.r#match;
+        }
+        match (iupac_expansion(a), iupac_expansion(b)) {
+            (Some(a), Some(b)) if a.iter().any(|base| b.contains(base)) => self.partial,
+            _ => self.miss_match,
+        }
+    }
+}
+
+/// Symbols of the BLOSUM62 matrix
+const BLOSUM62_SYMBOLS: &[u8; 24] = b"ARNDCQEGHILKMFPSTWYVBZX*";
+
+/// Symbols of the [NUC.4.4](https://ftp.ncbi.nih.gov/blast/matrices/NUC.4.4) matrix, in the
+/// order of its rows and columns
+const NUC44_SYMBOLS: &[u8; 15] = b"ATGCSWRYKMBVHDN";
+
+#[rustfmt::skip]
+const NUC44: [[i32; 15]; 15] = [
+    [ 5, -4, -4, -4, -4,  1,  1, -4, -4,  1, -4, -1, -1, -1, -2],
+    [-4,  5, -4, -4, -4,  1, -4,  1,  1, -4, -1, -4, -1, -1, -2],
+    [-4, -4,  5, -4,  1, -4,  1, -4,  1, -4, -1, -1, -4, -1, -2],
+    [-4, -4, -4,  5,  1, -4, -4,  1, -4,  1, -1, -1, -1, -4, -2],
+    [-4, -4,  1,  1, -1, -4, -2, -2, -2, -2, -1, -1, -3, -3, -1],
+    [ 1,  1, -4, -4, -4, -1, -2, -2, -2, -2, -3, -3, -1, -1, -1],
+    [ 1, -4,  1, -4, -2, -2, -1, -4, -2, -2, -3, -1, -3, -1, -1],
+    [-4,  1, -4,  1, -2, -2, -4, -1, -2, -2, -1, -3, -1, -3, -1],
+    [-4,  1,  1, -4, -2, -2, -2, -2, -1, -4, -1, -3, -3, -1, -1],
+    [ 1, -4, -4,  1, -2, -2, -2, -2, -4, -1, -3, -1, -1, -3, -1],
+    [-4, -1, -1, -1, -1, -3, -3, -1, -1, -3, -1, -2, -2, -2, -1],
+    [-1, -4, -1, -1, -1, -3, -1, -3, -3, -1, -2, -1, -2, -2, -1],
+    [-1, -1, -4, -1, -3, -1, -3, -1, -3, -1, -2, -2, -1, -2, -1],
+    [-1, -1, -1, -4, -3, -1, -1, -3, -1, -3, -2, -2, -2, -1, -1],
+    [-2, -2, -2, -2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+];
+
+/// Scoring rule with a score for every pair of bases, e.g. to penalize transversions more
+/// than transitions. Bases are compared case insensitively.
+#[derive(Debug, Clone)]
+pub struct MatrixScore {
+    scores: HashMap<(u8, u8), i32>,
+    /// Score of pairs missing from `scores`
+    fallback: i32,
+}
+
+impl Display for MatrixScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MatrixScore(pairs={},fallback={})",
+            self.scores.len(),
+            self.fallback
+        )
+    }
+}
+
+impl MatrixScore {
+    /// Matrix of `((a, b), score)` pairs, `(b, a)` scoring the same as `(a, b)` unless given
+    /// too. Pairs missing from the matrix get its lowest score.
+    pub fn from_pairs<I>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = ((u8, u8), i32)>,
+    {
+        let mut scores = HashMap::new();
+        for ((a, b), score) in pairs {
+            let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+            scores.insert((a, b), score);
+            scores.entry((b, a)).or_insert(score);
+        }
+        let fallback = scores.values().copied().min().unwrap_or(0);
+        tracing::info!("Generating MatrixScore of {} pairs", scores.len());
+        Self { scores, fallback }
+    }
+
+    /// NCBI's NUC.4.4 matrix, covering the IUPAC ambiguity codes
+    pub fn nuc44() -> Self {
+        Self::from_pairs(NUC44_SYMBOLS.iter().enumerate().flat_map(|(i, a)| {
+            NUC44_SYMBOLS
+                .iter()
+                .enumerate()
+                .map(move |(j, b)| ((*a, *b), NUC44[i][j]))
+        }))
+    }
+
+    /// BLOSUM62 matrix of amino acids, including the ambiguity codes `B`, `Z`, `X` and stop
+    /// `*`, for aligning proteins e.g. from [crate::translation::translate]
+    pub fn blosum62() -> Self {
+        Self::from_pairs(BLOSUM62_SYMBOLS.iter().flat_map(|a| {
+            BLOSUM62_SYMBOLS
+                .iter()
+                .map(move |b| ((*a, *b), bio::scores::blosum62(*a, *b)))
+        }))
+    }
+}
+
+impl MatchFunc for MatrixScore {
+    fn score(&self, a: u8, b: u8) -> i32 {
+        self.scores
+            .get(&(a.to_ascii_uppercase(), b.to_ascii_uppercase()))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+}
+
 /// Specifying gap penalty for Smith Waterman algorithm
 /// See: https://en.wikipedia.org/wiki/Smith_Waterman_algorithm#Gap_penalty
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct GapPanelty {
     pub open: i32,
     pub extend: i32,
@@ -91,12 +265,33 @@ impl Display for GapPanelty {
     }
 }
 
+/// Error of [GapPanelty::try_new], penalties must be negative
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum GapError {
+    #[error("Gap open penalty must be negative, got {0}")]
+    NonNegativeOpen(i32),
+    #[error("Gap extend penalty must be negative, got {0}")]
+    NonNegativeExtend(i32),
+}
+
 impl GapPanelty {
+    /// Panics if a penalty is not negative, see [GapPanelty::try_new]
     pub fn new(open: i32, extend: i32) -> Self {
-        assert!(open < 0, "Gap open penalty cant be positive");
-        assert!(extend < 0, "Gap extend penalty cant be positive");
+        match Self::try_new(open, extend) {
+            Ok(gap_penalty) => gap_penalty,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    pub fn try_new(open: i32, extend: i32) -> Result<Self, GapError> {
+        if open >= 0 {
+            return Err(GapError::NonNegativeOpen(open));
+        }
+        if extend >= 0 {
+            return Err(GapError::NonNegativeExtend(extend));
+        }
         tracing::info!("Generating GapPanelty open={} extend={}", open, extend);
-        Self { open, extend }
+        Ok(Self { open, extend })
     }
 }
 
@@ -106,6 +301,294 @@ impl From<(i32, i32)> for GapPanelty {
     }
 }
 
+/// Alignment algorithm: Needleman-Wunsch for global, Smith-Waterman for local, and
+/// semiglobal aligning the whole query with free overhangs on the reference
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AlignMode {
+    Global,
+    Local,
+    #[default]
+    Semiglobal,
+}
+
+/// Penalty of a gap of length `len`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapModel {
+    /// `penalty * len`
+    Linear(i32),
+    /// `open + extend * len`
+    Affine { open: i32, extend: i32 },
+}
+
+impl Display for GapModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear(penalty) => write!(f, "Gap(linear={penalty})"),
+            Self::Affine { open, extend } => write!(f, "Gap(open={open},extend={extend})"),
+        }
+    }
+}
+
+impl From<&GapPanelty> for GapModel {
+    fn from(value: &GapPanelty) -> Self {
+        Self::Affine {
+            open: value.open,
+            extend: value.extend,
+        }
+    }
+}
+
+/// Algorithm and gap model of [DiffStat::align]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentConfig {
+    pub mode: AlignMode,
+    pub gap_model: GapModel,
+}
+
+/// Operations of `alignment` without the reference-only (`Ins`) columns at either end, like
+/// the reference overhangs of a semiglobal alignment, along with the reference position of
+/// the first kept column. Clipping operations at the ends are dropped too.
+fn trim_reference_overhangs(alignment: &Alignment) -> (usize, &[AlignmentOperation]) {
+    let is_end = |operation: &&AlignmentOperation| {
+        matches!(
+            operation,
+            AlignmentOperation::Ins | AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+        )
+    };
+    let operations = alignment.operations.as_slice();
+    let leading = operations.iter().take_while(is_end).count();
+    let trailing = operations[leading..]
+        .iter()
+        .rev()
+        .take_while(is_end)
+        .count();
+    let overhang = operations[..leading]
+        .iter()
+        .filter(|operation| **operation == AlignmentOperation::Ins)
+        .count();
+    (
+        alignment.xstart + overhang,
+        &operations[leading..operations.len() - trailing],
+    )
+}
+
+/// Walk the alignment column by column, yielding each operation with the reference and
+/// query positions it starts at
+pub(crate) fn alignment_columns(
+    alignment: &Alignment,
+) -> impl Iterator<Item = (AlignmentOperation, usize, usize)> + '_ {
+    alignment.operations.iter().scan(
+        (alignment.xstart, alignment.ystart),
+        |(ref_pos, query_pos), operation| {
+            let column = (*operation, *ref_pos, *query_pos);
+            match operation {
+                AlignmentOperation::Match | AlignmentOperation::Subst => {
+                    *ref_pos += 1;
+                    *query_pos += 1;
+                }
+                AlignmentOperation::Ins => *ref_pos += 1,
+                AlignmentOperation::Del => *query_pos += 1,
+                _ => (),
+            }
+            Some(column)
+        },
+    )
+}
+
+/// A base of [DiffStat::to_html], escaped if it has a meaning in HTML
+fn html_escape(base: u8) -> String {
+    match base {
+        b'<' => "&lt;".to_string(),
+        b'>' => "&gt;".to_string(),
+        b'&' => "&amp;".to_string(),
+        _ => (base as char).to_string(),
+    }
+}
+
+/// Row of [DiffStat::alignment_table], `-` marks the gapped side
+#[derive(Tabled)]
+struct AlignmentColumn {
+    #[tabled(rename = "ref-pos")]
+    ref_pos: String,
+    #[tabled(rename = "query-pos")]
+    query_pos: String,
+    #[tabled(rename = "ref-base")]
+    ref_base: String,
+    #[tabled(rename = "query-base")]
+    query_base: String,
+    operation: &'static str,
+}
+
+/// Error of [DiffStat::pairwise_aligner_global_max_gaps] when the alignment has more gaps
+/// than allowed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Alignment has {gaps} gaps, more than the allowed {max_gaps}")]
+pub struct TooManyGaps {
+    pub gaps: usize,
+    pub max_gaps: usize,
+}
+
+/// Error of a distance that can't be calculated, see [DiffStat::try_hamming_distance]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DistanceError {
+    #[error(
+        "Hamming distance needs sequences of the same length, reference has {reference} bases \
+        and query {query}"
+    )]
+    UnequalLengths { reference: usize, query: usize },
+}
+
+/// Error of [DiffStat::validate_alphabet], with the `(position, byte)` of every symbol
+/// outside of the alphabet
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "Illegal symbols: {} in reference, {} in query",
+    reference.len(),
+    query.len()
+)]
+pub struct IllegalSymbols {
+    pub reference: Vec<(usize, u8)>,
+    pub query: Vec<(usize, u8)>,
+}
+
+/// Components of an alignment score, see [DiffStat::score_breakdown]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    /// Sum of the scores of matching columns
+    pub matches: i32,
+    /// Sum of the scores of substitutions
+    pub mismatches: i32,
+    /// Gap open penalty, once per gap
+    pub gap_open: i32,
+    /// Gap extend penalty, once per gapped column
+    pub gap_extend: i32,
+}
+
+impl ScoreBreakdown {
+    /// Sum of the components, the score of the alignment
+    pub fn total(&self) -> i32 {
+        self.matches + self.mismatches + self.gap_open + self.gap_extend
+    }
+}
+
+/// Bounds of the aligned part of the reference and query, 0-based and half-open (the end
+/// is the position after the last aligned base). See [DiffStat::aligned_region]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignedRegion {
+    pub ref_start: usize,
+    pub ref_end: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+}
+
+/// Strand of the query that got aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    /// Reverse complement of the query
+    Reverse,
+}
+
+/// Which strand to keep when the forward and reverse strand of a query align with the same
+/// score, see [TieBreak::choose]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    #[default]
+    PreferForward,
+    PreferReverse,
+    /// Keep the strand with higher identity, falling back to forward if that ties too
+    ByIdentity,
+}
+
+impl TieBreak {
+    /// Strand to keep out of the alignments of the forward and reverse strand of a query: the
+    /// higher scoring one, or the one picked by this policy on a tie
+    pub fn choose(self, forward: &Alignment, reverse: &Alignment) -> Strand {
+        match forward.score.cmp(&reverse.score) {
+            Ordering::Greater => Strand::Forward,
+            Ordering::Less => Strand::Reverse,
+            Ordering::Equal => match self {
+                TieBreak::PreferForward => Strand::Forward,
+                TieBreak::PreferReverse => Strand::Reverse,
+                TieBreak::ByIdentity if identity(reverse) > identity(forward) => Strand::Reverse,
+                TieBreak::ByIdentity => Strand::Forward,
+            },
+        }
+    }
+}
+
+/// Fraction of alignment columns that are matches
+pub(crate) fn identity(alignment: &PairwiseAlignment) -> f64 {
+    let columns = alignment
+        .operations
+        .iter()
+        .filter(|operation| {
+            !matches!(
+                operation,
+                AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+            )
+        })
+        .count();
+    let matches = alignment
+        .operations
+        .iter()
+        .filter(|operation| **operation == AlignmentOperation::Match)
+        .count();
+    if columns == 0 {
+        0.0
+    } else {
+        matches as f64 / columns as f64
+    }
+}
+
+/// Reverse complement of a DNA sequence, case is preserved. IUPAC ambiguity codes are
+/// complemented too (e.g. `R` into `Y`), anything else is kept as is.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|base| {
+            let complement = match base.to_ascii_uppercase() {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                b'R' => b'Y',
+                b'Y' => b'R',
+                b'K' => b'M',
+                b'M' => b'K',
+                b'B' => b'V',
+                b'V' => b'B',
+                b'D' => b'H',
+                b'H' => b'D',
+                _ => return *base,
+            };
+            if base.is_ascii_lowercase() {
+                complement.to_ascii_lowercase()
+            } else {
+                complement
+            }
+        })
+        .collect()
+}
+
+/// Homopolymer collapsed reference and query, with their run lengths
+#[derive(Debug)]
+struct Collapsed {
+    reference: Vec<u8>,
+    reference_runs: Vec<usize>,
+    query: Vec<u8>,
+    query_runs: Vec<usize>,
+}
+
+/// Callback of [DiffStat::set_progress]
+struct Progress(Box<dyn Fn(f32) + Send>);
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Progress")
+    }
+}
+
 /// Compare two sequences and align them
 #[derive(Debug)]
 pub struct DiffStat<'seq, F>
@@ -122,6 +605,16 @@ where
     score: F,
     /// Alignment of query sequence wrt reference
     alignment: Option<PairwiseAlignment>,
+    /// Strand of the query used for the alignment
+    strand: Option<Strand>,
+    /// Reverse complement of the query, kept when the reverse strand got aligned
+    reverse_query: Option<Vec<u8>>,
+    /// Strand choice on a tie during best strand alignment
+    tie_break: TieBreak,
+    /// Homopolymer collapsed sequences, aligned instead of the original ones when present
+    collapsed: Option<Collapsed>,
+    /// Called with the fraction of the alignment done
+    progress: Option<Progress>,
 }
 
 impl<'seq, F> AsRef<Self> for DiffStat<'seq, F>
@@ -149,7 +642,136 @@ where
             alignment: None,
             gap_penalty: gap_penalty.into(),
             score,
+            strand: None,
+            reverse_query: None,
+            tie_break: TieBreak::default(),
+            collapsed: None,
+            progress: None,
+        }
+    }
+
+    /// Report the progress of alignments to `cb`, called with the fraction (`0.0..=1.0`) done.
+    ///
+    /// The bio aligners can't be followed: they report `1.0` once done, the banded one `0.0`
+    /// at start too. [DiffStat::pairwise_aligner_windowed] reports after every window.
+    pub fn set_progress(&mut self, cb: Box<dyn Fn(f32) + Send>) {
+        self.progress = Some(Progress(cb));
+    }
+
+    fn report_progress(&self, fraction: f32) {
+        if let Some(Progress(cb)) = &self.progress {
+            cb(fraction);
+        }
+    }
+
+    /// Align homopolymer collapsed sequences (see [collapse_homopolymers]) instead of the
+    /// original ones, ignoring homopolymer length errors common in nanopore reads.
+    ///
+    /// Positions of the alignment are then in collapsed space, use
+    /// [DiffStat::original_reference_position] and [DiffStat::original_query_position] to map
+    /// them back. Changing this discards any existing alignment.
+    pub fn set_collapse_homopolymers(&mut self, collapse: bool) {
+        tracing::info!("Setting homopolymer collapsing to {}", collapse);
+        self.collapsed = collapse.then(|| {
+            let (reference, reference_runs) = collapse_homopolymers(self.reference);
+            let (query, query_runs) = collapse_homopolymers(self.query);
+            Collapsed {
+                reference,
+                reference_runs,
+                query,
+                query_runs,
+            }
+        });
+        self.alignment = None;
+        self.strand = None;
+        self.reverse_query = None;
+    }
+
+    /// Map a reference position of the alignment back to the original reference, this is the
+    /// identity unless homopolymers are collapsed
+    pub fn original_reference_position(&self, pos: usize) -> usize {
+        self.collapsed.as_ref().map_or(pos, |collapsed| {
+            expand_position(&collapsed.reference_runs, pos)
+        })
+    }
+
+    /// Map a (forward strand) query position of the alignment back to the original query, this
+    /// is the identity unless homopolymers are collapsed
+    pub fn original_query_position(&self, pos: usize) -> usize {
+        self.collapsed
+            .as_ref()
+            .map_or(pos, |collapsed| expand_position(&collapsed.query_runs, pos))
+    }
+
+    /// Check that both the (original) reference and query are written in `alphabet`, e.g. to
+    /// catch stray characters of a FASTA file before they skew the scoring
+    pub fn validate_alphabet(&self, alphabet: Alphabet) -> Result<(), IllegalSymbols> {
+        tracing::info!("Validating sequences against {:?} alphabet", alphabet);
+        let illegal = IllegalSymbols {
+            reference: alphabet.invalid_symbols(self.reference),
+            query: alphabet.invalid_symbols(self.query),
+        };
+        if illegal.reference.is_empty() && illegal.query.is_empty() {
+            Ok(())
+        } else {
+            Err(illegal)
+        }
+    }
+
+    /// Set how ties are resolved by [DiffStat::pairwise_aligner_semiglobal_best_strand]
+    pub fn set_tie_break(&mut self, tie_break: TieBreak) {
+        self.tie_break = tie_break;
+    }
+
+    /// Strand of the query that got aligned, `None` before alignment
+    pub fn strand(&self) -> Option<Strand> {
+        self.strand
+    }
+
+    /// Master sequence, homopolymer collapsed if enabled
+    pub fn reference(&self) -> &[u8] {
+        self.collapsed
+            .as_ref()
+            .map_or(self.reference, |collapsed| &collapsed.reference)
+    }
+
+    /// Sequence aligned against the reference, homopolymer collapsed if enabled. This is the
+    /// reverse complement of the query if its reverse strand got aligned.
+    pub fn query(&self) -> &[u8] {
+        self.reverse_query
+            .as_deref()
+            .unwrap_or_else(|| self.forward_query())
+    }
+
+    /// Warn when the query is more than 10 times longer than the reference, which usually
+    /// means they got swapped: semiglobal alignment treats them asymmetrically. Returns whether
+    /// the pair looks suspicious.
+    pub fn warn_if_suspicious(&self) -> bool {
+        let (reference, query) = (self.reference().len(), self.forward_query().len());
+        let suspicious = query > reference.saturating_mul(SUSPICIOUS_LENGTH_RATIO);
+        if suspicious {
+            tracing::warn!(
+                "Query ({} bp) is more than {}x longer than the reference ({} bp), were they swapped?",
+                query,
+                SUSPICIOUS_LENGTH_RATIO,
+                reference
+            );
         }
+        suspicious
+    }
+
+    /// Forward strand of the query, homopolymer collapsed if enabled
+    fn forward_query(&self) -> &[u8] {
+        self.collapsed
+            .as_ref()
+            .map_or(self.query, |collapsed| &collapsed.query)
+    }
+
+    fn set_forward_alignment(&mut self, alignment: PairwiseAlignment) {
+        self.report_progress(1.0);
+        self.alignment = Some(alignment);
+        self.strand = Some(Strand::Forward);
+        self.reverse_query = None;
     }
 
     /// Calculate [Levenshtein](https://en.wikipedia.org/wiki/Levenshtein_distance) distance
@@ -164,29 +786,82 @@ where
     }
 
     /// Calculate [Hamming](https://en.wikipedia.org/wiki/Hamming_distance) distance
+    ///
+    /// # Panics
+    ///
+    /// If the reference and query have different lengths, see
+    /// [DiffStat::try_hamming_distance]
     pub fn hamming_distance(&self) -> u64 {
         tracing::info!("Calculating Hamming distance");
         hamming(self.reference, self.query)
     }
 
+    /// [DiffStat::hamming_distance], failing instead of panicking if the reference and query
+    /// have different lengths
+    pub fn try_hamming_distance(&self) -> Result<u64, DistanceError> {
+        if self.reference.len() != self.query.len() {
+            return Err(DistanceError::UnequalLengths {
+                reference: self.reference.len(),
+                query: self.query.len(),
+            });
+        }
+        Ok(self.hamming_distance())
+    }
+
+    /// Panics like [DiffStat::hamming_distance]
     pub fn hamming_distance_simd(&self) -> u64 {
         tracing::info!("Calculating Hamming distance(simd)");
         bio::alignment::distance::simd::hamming(self.reference, self.query)
     }
 
+    /// Distance between the reference and query with any [DistanceMetric]
+    pub fn distance<M: DistanceMetric>(&self, metric: M) -> u64 {
+        metric.distance(self.reference, self.query)
+    }
+
     fn aligner(&self) -> bio::alignment::pairwise::Aligner<F>
+    where
+        F: MatchFunc,
+    {
+        self.aligner_with_gaps(self.gap_penalty.open, self.gap_penalty.extend)
+    }
+
+    fn aligner_with_gaps(&self, open: i32, extend: i32) -> bio::alignment::pairwise::Aligner<F>
     where
         F: MatchFunc,
     {
         bio::alignment::pairwise::Aligner::with_capacity(
-            self.reference.len(),
-            self.query.len(),
-            self.gap_penalty.open,
-            self.gap_penalty.extend,
+            self.reference().len(),
+            self.forward_query().len(),
+            open,
+            extend,
             self.score.clone(),
         )
     }
 
+    /// Pairwise alignment with the algorithm and gap model of `config`, which takes precedence
+    /// over the gap penalty of this [DiffStat]
+    pub fn align(&mut self, config: &AlignmentConfig) {
+        tracing::info!(
+            "Performing pairwise alignment ({:?}) using {} and {}",
+            config.mode,
+            config.gap_model,
+            self.score
+        );
+        // A linear gap model is an affine one without opening penalty
+        let (open, extend) = match config.gap_model {
+            GapModel::Linear(penalty) => (0, penalty),
+            GapModel::Affine { open, extend } => (open, extend),
+        };
+        let mut aligner = self.aligner_with_gaps(open, extend);
+        let alignment = match config.mode {
+            AlignMode::Global => aligner.global(self.reference(), self.forward_query()),
+            AlignMode::Local => aligner.local(self.reference(), self.forward_query()),
+            AlignMode::Semiglobal => aligner.semiglobal(self.reference(), self.forward_query()),
+        };
+        self.set_forward_alignment(alignment);
+    }
+
     /// Pairwise alignment using Smith Waterman algorithm (Semiglobal)
     pub fn pairwise_aligner_semiglobal(&mut self) {
         tracing::info!(
@@ -194,7 +869,10 @@ where
             self.gap_penalty,
             self.score
         );
-        self.alignment = Some(self.aligner().semiglobal(self.reference, self.query));
+        let alignment = self
+            .aligner()
+            .semiglobal(self.reference(), self.forward_query());
+        self.set_forward_alignment(alignment);
     }
 
     /// Pairwise alignment using Smith Waterman algorithm (Global)
@@ -204,7 +882,10 @@ where
             self.gap_penalty,
             self.score
         );
-        self.alignment = Some(self.aligner().global(self.reference, self.query));
+        let alignment = self
+            .aligner()
+            .global(self.reference(), self.forward_query());
+        self.set_forward_alignment(alignment);
     }
 
     /// Pairwise alignment using Smith Waterman algorithm (Local)
@@ -214,7 +895,313 @@ where
             self.gap_penalty,
             self.score
         );
-        self.alignment = Some(self.aligner().local(self.reference, self.query));
+        let alignment = self.aligner().local(self.reference(), self.forward_query());
+        self.set_forward_alignment(alignment);
+    }
+
+    /// Up to `max_hits` local alignments scoring at least `min_score`, best first, e.g. every
+    /// copy of a repeated motif. The alignment of this [DiffStat] is left untouched.
+    ///
+    /// Hits are found one at a time: after each hit the reference bases it covers are masked
+    /// with a symbol that is a mismatch under [Score], so with that scoring the next search
+    /// can't overlap it on the reference. Hits may still share query bases.
+    pub fn local_alignments(&self, min_score: i32, max_hits: usize) -> Vec<Alignment> {
+        tracing::info!(
+            "Finding up to {} local alignments scoring at least {} using {} and {}",
+            max_hits,
+            min_score,
+            self.gap_penalty,
+            self.score
+        );
+        let mut reference = self.reference().to_vec();
+        let mut aligner = self.aligner();
+        let mut hits = Vec::new();
+        while hits.len() < max_hits {
+            let alignment = aligner.local(&reference, self.forward_query());
+            if alignment.operations.is_empty() || alignment.score < min_score {
+                break;
+            }
+            reference[alignment.xstart..alignment.xend].fill(MASK);
+            hits.push(alignment);
+        }
+        hits
+    }
+
+    /// Pairwise alignment (Global) restricted to a band around matching `k`-mers, widened by
+    /// `w` bases on each side. Much faster than [DiffStat::pairwise_aligner_global] for long,
+    /// similar sequences like whole genomes, but may miss the optimal alignment if it leaves
+    /// the band.
+    pub fn pairwise_aligner_banded_global(&mut self, k: usize, w: usize) {
+        tracing::info!(
+            "Performing banded pairwise alignment (global) with k={} w={} using {} and {}",
+            k,
+            w,
+            self.gap_penalty,
+            self.score
+        );
+        self.report_progress(0.0);
+        let alignment = bio::alignment::pairwise::banded::Aligner::with_capacity(
+            self.reference().len(),
+            self.forward_query().len(),
+            self.gap_penalty.open,
+            self.gap_penalty.extend,
+            self.score.clone(),
+            k,
+            w,
+        )
+        .global(self.reference(), self.forward_query());
+        self.set_forward_alignment(alignment);
+    }
+
+    /// Pairwise alignment (Global) computed window by window, so the dynamic programming
+    /// matrices are at most `window * window` instead of covering both whole sequences.
+    ///
+    /// Windows of `window` bases of both sequences are globally aligned, and only the
+    /// alignment columns until `window - overlap` bases of either sequence are kept: the end of
+    /// the window, forced to align by the global alignment, is aligned again at the start of
+    /// the next one. Indels longer than `overlap`, or placed differently on both sides of a
+    /// window boundary, may still be misaligned, so the result can score below
+    /// [DiffStat::pairwise_aligner_global]. The score is recalculated over the stitched
+    /// alignment.
+    ///
+    /// # Panics
+    ///
+    /// If `overlap` is not smaller than `window`.
+    pub fn pairwise_aligner_windowed(&mut self, window: usize, overlap: usize) {
+        assert!(
+            overlap < window,
+            "Window overlap must be smaller than the window"
+        );
+        tracing::info!(
+            "Performing windowed pairwise alignment (global) with window={} overlap={} using {} and {}",
+            window,
+            overlap,
+            self.gap_penalty,
+            self.score
+        );
+        let (reference, query) = (self.reference(), self.forward_query());
+        let mut aligner = bio::alignment::pairwise::Aligner::with_capacity(
+            window,
+            window,
+            self.gap_penalty.open,
+            self.gap_penalty.extend,
+            self.score.clone(),
+        );
+        let keep = window - overlap;
+        let (mut ref_pos, mut query_pos) = (0, 0);
+        let mut operations = Vec::new();
+        loop {
+            let ref_end = reference.len().min(ref_pos + window);
+            let query_end = query.len().min(query_pos + window);
+            let alignment =
+                aligner.global(&reference[ref_pos..ref_end], &query[query_pos..query_end]);
+            if ref_end == reference.len() && query_end == query.len() {
+                operations.extend(alignment.operations);
+                break;
+            }
+            let (mut ref_used, mut query_used) = (0, 0);
+            for operation in alignment.operations {
+                if ref_used >= keep || query_used >= keep {
+                    break;
+                }
+                match operation {
+                    AlignmentOperation::Match | AlignmentOperation::Subst => {
+                        ref_used += 1;
+                        query_used += 1;
+                    }
+                    AlignmentOperation::Ins => ref_used += 1,
+                    AlignmentOperation::Del => query_used += 1,
+                    _ => (),
+                }
+                operations.push(operation);
+            }
+            ref_pos += ref_used;
+            query_pos += query_used;
+            self.report_progress(
+                (ref_pos + query_pos) as f32 / (reference.len() + query.len()) as f32,
+            );
+        }
+
+        let alignment = Alignment {
+            score: 0,
+            xstart: 0,
+            ystart: 0,
+            xend: reference.len(),
+            yend: query.len(),
+            xlen: reference.len(),
+            ylen: query.len(),
+            operations,
+            mode: AlignmentMode::Global,
+        };
+        self.set_forward_alignment(alignment);
+        let score = self.score_breakdown().map(|breakdown| breakdown.total());
+        if let (Some(alignment), Some(score)) = (self.alignment.as_mut(), score) {
+            alignment.score = score;
+        }
+    }
+
+    /// Pairwise alignment (Global), rejected when it has more than `max_gaps` gaps. A gap is a
+    /// run of consecutive insertions or deletions, however long.
+    ///
+    /// On error the alignment is cleared, so callers can fall back to another strategy or skip
+    /// the pair.
+    pub fn pairwise_aligner_global_max_gaps(&mut self, max_gaps: usize) -> Result<(), TooManyGaps> {
+        self.pairwise_aligner_global();
+        let gaps = self.alignment.as_ref().map_or(0, |alignment| {
+            let mut previous = None;
+            alignment
+                .operations
+                .iter()
+                .filter(|&&operation| {
+                    let opens =
+                        matches!(operation, AlignmentOperation::Ins | AlignmentOperation::Del)
+                            && previous != Some(operation);
+                    previous = Some(operation);
+                    opens
+                })
+                .count()
+        });
+        if gaps > max_gaps {
+            self.alignment = None;
+            self.strand = None;
+            return Err(TooManyGaps { gaps, max_gaps });
+        }
+        Ok(())
+    }
+
+    /// Global alignment computed directly with Gotoh's three matrix affine gap algorithm,
+    /// instead of delegating to bio. Meant for validating and teaching the affine gap
+    /// handling, [DiffStat::pairwise_aligner_global] is the one to use otherwise.
+    ///
+    /// `M` holds the best score of alignments ending in an aligned pair, `Ix` ending in a
+    /// reference base against a gap and `Iy` ending in a query base against a gap. A gap of
+    /// length `k` costs `open + extend * k`, as configured in the [GapPanelty].
+    pub fn gotoh_global(&self) -> Alignment {
+        tracing::info!(
+            "Performing Gotoh global alignment using {} and {}",
+            self.gap_penalty,
+            self.score
+        );
+        let (reference, query) = (self.reference(), self.forward_query());
+        let (open, extend) = (self.gap_penalty.open, self.gap_penalty.extend);
+        let (rows, cols) = (reference.len() + 1, query.len() + 1);
+        // Low enough to never win, high enough to not overflow when a penalty is added
+        let min = i32::MIN / 2;
+        const M: usize = 0;
+        const IX: usize = 1;
+        const IY: usize = 2;
+
+        // Scores and the matrix each cell was reached from, per matrix
+        let mut scores = [
+            vec![vec![min; cols]; rows],
+            vec![vec![min; cols]; rows],
+            vec![vec![min; cols]; rows],
+        ];
+        let mut from = [
+            vec![vec![M; cols]; rows],
+            vec![vec![M; cols]; rows],
+            vec![vec![M; cols]; rows],
+        ];
+        scores[M][0][0] = 0;
+        for i in 1..rows {
+            scores[IX][i][0] = open + extend * i as i32;
+            from[IX][i][0] = if i == 1 { M } else { IX };
+        }
+        for j in 1..cols {
+            scores[IY][0][j] = open + extend * j as i32;
+            from[IY][0][j] = if j == 1 { M } else { IY };
+        }
+        // Best predecessor among the matrices, given the cost of moving from each of them
+        let best = |cells: [i32; 3]| {
+            (0..3)
+                .rev()
+                .max_by_key(|&state| cells[state])
+                .map(|state| (cells[state], state))
+                .expect("There are 3 matrices")
+        };
+        for i in 1..rows {
+            for j in 1..cols {
+                let diagonal = [M, IX, IY].map(|state| scores[state][i - 1][j - 1]);
+                let (score, state) = best(diagonal);
+                scores[M][i][j] = score + self.score.score(reference[i - 1], query[j - 1]);
+                from[M][i][j] = state;
+
+                let up = [M, IX, IY].map(|state| {
+                    scores[state][i - 1][j] + if state == IX { extend } else { open + extend }
+                });
+                (scores[IX][i][j], from[IX][i][j]) = best(up);
+
+                let left = [M, IX, IY].map(|state| {
+                    scores[state][i][j - 1] + if state == IY { extend } else { open + extend }
+                });
+                (scores[IY][i][j], from[IY][i][j]) = best(left);
+            }
+        }
+
+        let (score, mut state) = best([M, IX, IY].map(|state| scores[state][rows - 1][cols - 1]));
+        let mut operations = Vec::with_capacity(rows + cols);
+        let (mut i, mut j) = (rows - 1, cols - 1);
+        while i > 0 || j > 0 {
+            let previous = from[state][i][j];
+            match state {
+                M => {
+                    operations.push(if reference[i - 1] == query[j - 1] {
+                        AlignmentOperation::Match
+                    } else {
+                        AlignmentOperation::Subst
+                    });
+                    i -= 1;
+                    j -= 1;
+                }
+                IX => {
+                    operations.push(AlignmentOperation::Ins);
+                    i -= 1;
+                }
+                _ => {
+                    operations.push(AlignmentOperation::Del);
+                    j -= 1;
+                }
+            }
+            state = previous;
+        }
+        operations.reverse();
+
+        Alignment {
+            score,
+            xstart: 0,
+            ystart: 0,
+            xend: reference.len(),
+            yend: query.len(),
+            xlen: reference.len(),
+            ylen: query.len(),
+            operations,
+            mode: AlignmentMode::Global,
+        }
+    }
+
+    /// Pairwise alignment (Semiglobal) of both the query and its reverse complement, keeping
+    /// the higher scoring one. Ties are resolved by [TieBreak], see [DiffStat::set_tie_break].
+    pub fn pairwise_aligner_semiglobal_best_strand(&mut self) {
+        tracing::info!(
+            "Performing pairwise alignment (semiglobal) of both strands using {} and {}",
+            self.gap_penalty,
+            self.score
+        );
+        let reverse_query = reverse_complement(self.forward_query());
+        let mut aligner = self.aligner();
+        let forward = aligner.semiglobal(self.reference(), self.forward_query());
+        let reverse = aligner.semiglobal(self.reference(), &reverse_query);
+
+        let strand = self.tie_break.choose(&forward, &reverse);
+        tracing::info!("Keeping {:?} strand", strand);
+        match strand {
+            Strand::Forward => self.set_forward_alignment(forward),
+            Strand::Reverse => {
+                self.alignment = Some(reverse);
+                self.strand = Some(Strand::Reverse);
+                self.reverse_query = Some(reverse_query);
+            }
+        }
     }
 
     /// CAUTION: Use for small sequence only, its running time complexity is
@@ -230,32 +1217,1650 @@ where
         S: AsRef<[u8]>,
     {
         tracing::info!("Performing pairwise alignment (semiglobal)");
-        let mut aligner = bio::alignment::poa::Aligner::new(scoring, self.reference);
+        let mut aligner = bio::alignment::poa::Aligner::new(scoring, self.reference());
         references.into_iter().flatten().for_each(|reference| {
             aligner.global(reference.as_ref()).add_to_graph();
         });
-        aligner.global(self.query.as_ref()).alignment()
+        aligner.global(self.forward_query()).alignment()
     }
 
     /// Pretty print the alignment, see [bio::alignment::Alignment::pretty]
     pub fn pretty_print(&self, coloumn: usize) {
         tracing::info!("Pretty print with {} coloumns", coloumn);
-        if let Some(pretty) = self
-            .alignment
-            .as_ref()
-            .map(|alignment| alignment.pretty(self.reference, self.query, coloumn))
-        {
+        if let Some(pretty) = self.pretty_string(coloumn) {
             println!("{pretty}")
         }
     }
 
+    /// [DiffStat::pretty_print] fitting the width of the terminal, or 120 columns when not
+    /// printing to one
+    pub fn pretty_print_auto(&self) {
+        self.pretty_print(terminal_pretty_columns())
+    }
+
+    /// Rendered alignment of [DiffStat::pretty_print], without printing it. `None` before
+    /// alignment.
     pub fn pretty_string(&self, coloumn: usize) -> Option<String> {
         self.alignment
             .as_ref()
-            .map(|alignment| alignment.pretty(self.reference, self.query, coloumn))
+            .map(|alignment| alignment.pretty(self.reference(), self.query(), coloumn))
+    }
+
+    /// [DiffStat::pretty_string] fitting the width of the terminal, like
+    /// [DiffStat::pretty_print_auto]
+    pub fn pretty_string_auto(&self) -> Option<String> {
+        self.pretty_string(terminal_pretty_columns())
+    }
+
+    /// The alignment as a self-contained HTML `<pre>` block, reference over query, wrapping
+    /// every `wrap` columns (`0` for no wrapping). `None` before alignment.
+    ///
+    /// Columns are `span`s of class `match` (gray), `subst` (red), `ins` (green, query base
+    /// missing from the reference) or `del` (struck through, reference base missing from the
+    /// query), styled inline.
+    pub fn to_html(&self, wrap: usize) -> Option<String> {
+        let alignment = self.alignment.as_ref()?;
+        let (reference, query) = (self.reference(), self.query());
+        let columns = alignment_columns(alignment)
+            .filter_map(|(operation, ref_pos, query_pos)| match operation {
+                AlignmentOperation::Match => Some(("match", reference[ref_pos], query[query_pos])),
+                AlignmentOperation::Subst => Some(("subst", reference[ref_pos], query[query_pos])),
+                AlignmentOperation::Del => Some(("ins", b'-', query[query_pos])),
+                AlignmentOperation::Ins => Some(("del", reference[ref_pos], b'-')),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut html = String::from("<pre style=\"font-family: monospace\">\n");
+        let wrap = if wrap == 0 {
+            columns.len().max(1)
+        } else {
+            wrap
+        };
+        for chunk in columns.chunks(wrap) {
+            for row in [
+                chunk
+                    .iter()
+                    .map(|(class, base, _)| (*class, *base))
+                    .collect::<Vec<_>>(),
+                chunk
+                    .iter()
+                    .map(|(class, _, base)| (*class, *base))
+                    .collect(),
+            ] {
+                for run in row.chunk_by(|a, b| a.0 == b.0) {
+                    let class = run[0].0;
+                    let style = match class {
+                        "match" => "color: gray",
+                        "subst" => "color: red",
+                        "ins" => "color: green",
+                        _ => "text-decoration: line-through",
+                    };
+                    let bases = run
+                        .iter()
+                        .map(|(_, base)| html_escape(*base))
+                        .collect::<String>();
+                    html.push_str(&format!(
+                        "<span class=\"{class}\" style=\"{style}\">{bases}</span>"
+                    ));
+                }
+                html.push('\n');
+            }
+            html.push('\n');
+        }
+        html.push_str("</pre>\n");
+        Some(html)
     }
 
     pub fn alignment(&self) -> Option<&Alignment> {
         self.alignment.as_ref()
     }
+
+    /// Score of the computed alignment
+    pub fn alignment_score(&self) -> Option<i32> {
+        self.alignment.as_ref().map(|alignment| alignment.score)
+    }
+
+    /// Whether an alignment has been computed
+    pub fn is_aligned(&self) -> bool {
+        self.alignment.is_some()
+    }
+
+    /// Where the alignment starts and ends in the reference and (aligned strand of the) query,
+    /// the whole sequences for a global alignment
+    pub fn aligned_region(&self) -> Option<AlignedRegion> {
+        let alignment = self.alignment.as_ref()?;
+        Some(AlignedRegion {
+            ref_start: alignment.xstart,
+            ref_end: alignment.xend,
+            query_start: alignment.ystart,
+            query_end: alignment.yend,
+        })
+    }
+
+    /// Unaligned (clipped) query bases before and after the aligned region, e.g. adapters
+    /// left out of a semiglobal or local alignment
+    pub fn clipped_sequences(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let alignment = self.alignment.as_ref()?;
+        let query = self.query();
+        Some((
+            query[..alignment.ystart].to_vec(),
+            query[alignment.yend..].to_vec(),
+        ))
+    }
+
+    /// Split the alignment score into the contribution of matches, substitutions, gap opens
+    /// and gap extensions, summing up to the score (clipping being free)
+    pub fn score_breakdown(&self) -> Option<ScoreBreakdown> {
+        let alignment = self.alignment.as_ref()?;
+        let (reference, query) = (self.reference(), self.query());
+        let mut previous = None;
+        let breakdown = alignment_columns(alignment).fold(
+            ScoreBreakdown::default(),
+            |mut breakdown, (operation, ref_pos, query_pos)| {
+                match operation {
+                    AlignmentOperation::Match => {
+                        breakdown.matches += self.score.score(reference[ref_pos], query[query_pos])
+                    }
+                    AlignmentOperation::Subst => {
+                        breakdown.mismatches +=
+                            self.score.score(reference[ref_pos], query[query_pos])
+                    }
+                    AlignmentOperation::Ins | AlignmentOperation::Del => {
+                        if previous != Some(operation) {
+                            breakdown.gap_open += self.gap_penalty.open;
+                        }
+                        breakdown.gap_extend += self.gap_penalty.extend;
+                    }
+                    _ => (),
+                }
+                previous = Some(operation);
+                breakdown
+            },
+        );
+        Some(breakdown)
+    }
+
+    /// Number of alignment columns between the first and the last aligned (match or
+    /// substitution) column, leaving out leading and trailing gaps, e.g. the reference
+    /// overhangs of a semiglobal alignment.
+    pub fn core_aligned_length(&self) -> Option<usize> {
+        let alignment = self.alignment.as_ref()?;
+        let columns = alignment
+            .operations
+            .iter()
+            .filter(|operation| {
+                !matches!(
+                    operation,
+                    AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+                )
+            })
+            .collect::<Vec<_>>();
+        let is_aligned = |operation: &AlignmentOperation| {
+            matches!(
+                operation,
+                AlignmentOperation::Match | AlignmentOperation::Subst
+            )
+        };
+        let first = columns.iter().position(|operation| is_aligned(operation));
+        let last = columns.iter().rposition(|operation| is_aligned(operation));
+        Some(first.zip(last).map_or(0, |(first, last)| last - first + 1))
+    }
+
+    /// Percentage (`0.0..=100.0`) of matching columns of the alignment.
+    ///
+    /// Internal gaps count toward the aligned length but terminal gaps don't, see
+    /// [DiffStat::core_aligned_length], so overhangs of a semiglobal alignment or length
+    /// differences at the ends of a global one don't lower the identity. `0.0` when no base is
+    /// aligned.
+    pub fn percent_identity(&self) -> Option<f64> {
+        let aligned_length = self.core_aligned_length()?;
+        let matches = self
+            .alignment
+            .as_ref()?
+            .operations
+            .iter()
+            .filter(|operation| **operation == AlignmentOperation::Match)
+            .count();
+        Some(if aligned_length == 0 {
+            0.0
+        } else {
+            100.0 * matches as f64 / aligned_length as f64
+        })
+    }
+
+    /// Transitions, transversions and compared sites (columns with a base on both sides) of
+    /// the alignment, gapped columns and substitutions with an ambiguous base being left out
+    fn substitution_sites(&self) -> Option<(usize, usize, usize)> {
+        let alignment = self.alignment.as_ref()?;
+        let (reference, query) = (self.reference(), self.query());
+        Some(alignment_columns(alignment).fold(
+            (0, 0, 0),
+            |(transitions, transversions, sites), (operation, ref_pos, query_pos)| match operation {
+                AlignmentOperation::Match => (transitions, transversions, sites + 1),
+                AlignmentOperation::Subst
+                    if is_transition(reference[ref_pos], query[query_pos]) =>
+                {
+                    (transitions + 1, transversions, sites + 1)
+                }
+                AlignmentOperation::Subst
+                    if is_transversion(reference[ref_pos], query[query_pos]) =>
+                {
+                    (transitions, transversions + 1, sites + 1)
+                }
+                _ => (transitions, transversions, sites),
+            },
+        ))
+    }
+
+    /// [Jukes-Cantor](https://en.wikipedia.org/wiki/Models_of_DNA_evolution#JC69_model_(Jukes_and_Cantor_1969))
+    /// distance, substitutions per site corrected for multiple substitutions:
+    /// `-3/4 ln(1 - 4/3 p)` with `p` the fraction of differing sites, gapped columns and
+    /// substitutions with an ambiguous base excluded.
+    ///
+    /// `None` before alignment, without any compared site, or when `p` reaches `3/4`.
+    pub fn jukes_cantor_distance(&self) -> Option<f64> {
+        let (transitions, transversions, sites) = self.substitution_sites()?;
+        if sites == 0 {
+            return None;
+        }
+        let p = (transitions + transversions) as f64 / sites as f64;
+        let arg = 1.0 - 4.0 * p / 3.0;
+        (arg > 0.0).then(|| -0.75 * arg.ln())
+    }
+
+    /// [Kimura 2-parameter](https://en.wikipedia.org/wiki/Models_of_DNA_evolution#K80_model_(Kimura_1980))
+    /// distance, telling transitions (`P`) and transversions (`Q`) apart:
+    /// `-1/2 ln(1 - 2P - Q) - 1/4 ln(1 - 2Q)`, gapped columns and substitutions with an
+    /// ambiguous base excluded.
+    ///
+    /// `None` before alignment, without any compared site, or when a logarithm is undefined.
+    pub fn kimura_2p_distance(&self) -> Option<f64> {
+        let (transitions, transversions, sites) = self.substitution_sites()?;
+        if sites == 0 {
+            return None;
+        }
+        let p = transitions as f64 / sites as f64;
+        let q = transversions as f64 / sites as f64;
+        let (arg_p, arg_q) = (1.0 - 2.0 * p - q, 1.0 - 2.0 * q);
+        (arg_p > 0.0 && arg_q > 0.0).then(|| -0.5 * arg_p.ln() - 0.25 * arg_q.ln())
+    }
+
+    /// JSON summary of the alignment: its score, [DiffStat::percent_identity], length (number of
+    /// columns) and [MutationStats](crate::mutation_detection::MutationStats) under
+    /// `mutations`. Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn summary_json(&self) -> Option<String> {
+        let alignment = self.alignment.as_ref()?;
+        let length = alignment
+            .operations
+            .iter()
+            .filter(|operation| {
+                !matches!(
+                    operation,
+                    AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+                )
+            })
+            .count();
+        let summary = serde_json::json!({
+            "score": alignment.score,
+            "percent_identity": self.percent_identity()?,
+            "length": length,
+            "mutations": crate::mutation_detection::Muatation::from(self).mutation_score()?,
+        });
+        Some(summary.to_string())
+    }
+
+    /// Per base dump of the alignment, one row per alignment column with the reference and
+    /// query positions, their bases and the operation (`-` marks the gapped side)
+    pub fn alignment_table(&self) -> Option<Table> {
+        let alignment = self.alignment.as_ref()?;
+        let (reference, query) = (self.reference(), self.query());
+        let rows = alignment_columns(alignment).filter_map(|(operation, ref_pos, query_pos)| {
+            let (has_ref, has_query, name) = match operation {
+                AlignmentOperation::Match => (true, true, "match"),
+                AlignmentOperation::Subst => (true, true, "substitution"),
+                AlignmentOperation::Ins => (true, false, "deletion"),
+                AlignmentOperation::Del => (false, true, "insertion"),
+                _ => return None,
+            };
+            let cell = |present: bool, value: String| if present { value } else { "-".to_string() };
+            Some(AlignmentColumn {
+                ref_pos: cell(has_ref, ref_pos.to_string()),
+                query_pos: cell(has_query, query_pos.to_string()),
+                ref_base: cell(has_ref, (reference[ref_pos] as char).to_string()),
+                query_base: cell(has_query, (query[query_pos] as char).to_string()),
+                operation: name,
+            })
+        });
+        Some(Table::new(rows))
+    }
+
+    /// Run-length encoded operations of the alignment, consecutive identical operations being
+    /// grouped as `(operation, count)`. `None` before alignment.
+    ///
+    /// Operations keep bio's naming, see [IndelKind](crate::mutation_detection::IndelKind):
+    /// [AlignmentOperation::Del] is a base only in the query.
+    pub fn operation_runs(&self) -> Option<Vec<(AlignmentOperation, usize)>> {
+        let alignment = self.alignment.as_ref()?;
+        let mut runs: Vec<(AlignmentOperation, usize)> = Vec::new();
+        for operation in &alignment.operations {
+            match runs.last_mut() {
+                Some((last, count)) if last == operation => *count += 1,
+                _ => runs.push((*operation, 1)),
+            }
+        }
+        Some(runs)
+    }
+
+    /// CIGAR of the query against the reference, with `M` for aligned bases, `I` for bases
+    /// only in the query and `D` for bases only in the reference. Unaligned ends of the query
+    /// are clipped, hard (`H`) if `hard_clip` is set or soft (`S`) otherwise, while reference
+    /// overhangs (e.g. of a semiglobal alignment) are left out, so it never starts or ends
+    /// with `D`.
+    ///
+    /// [bio::alignment::Alignment::cigar] can't be used as it treats `x` as the query and
+    /// panics for global and local alignments.
+    pub fn cigar(&self, hard_clip: bool) -> Option<String> {
+        let alignment = self.alignment.as_ref()?;
+        let clip = if hard_clip { 'H' } else { 'S' };
+        let mut runs: Vec<(char, usize)> = Vec::new();
+        let mut push = |op: char, len: usize| match runs.last_mut() {
+            Some((last, count)) if *last == op => *count += len,
+            _ if len > 0 => runs.push((op, len)),
+            _ => (),
+        };
+        push(clip, alignment.ystart);
+        let (_, operations) = trim_reference_overhangs(alignment);
+        for operation in operations {
+            match operation {
+                AlignmentOperation::Match | AlignmentOperation::Subst => push('M', 1),
+                AlignmentOperation::Del => push('I', 1),
+                AlignmentOperation::Ins => push('D', 1),
+                _ => (),
+            }
+        }
+        push(clip, self.query().len() - alignment.yend);
+        Some(runs.iter().map(|(op, len)| format!("{len}{op}")).collect())
+    }
+
+    /// The alignment as a single SAM line of `qname` against `rname`, with a soft clipped
+    /// CIGAR and the alignment score as `AS:i:` tag. POS is the first reference base aligned
+    /// to the query, past any reference overhang. MAPQ and QUAL are unavailable.
+    pub fn to_sam_record(&self, qname: &str, rname: &str) -> Option<String> {
+        let alignment = self.alignment.as_ref()?;
+        let flag = if self.strand == Some(Strand::Reverse) {
+            16
+        } else {
+            0
+        };
+        let seq = if self.query().is_empty() {
+            "*".into()
+        } else {
+            String::from_utf8_lossy(self.query())
+        };
+        Some(format!(
+            "{qname}\t{flag}\t{rname}\t{}\t255\t{}\t*\t0\t0\t{seq}\t*\tAS:i:{}",
+            trim_reference_overhangs(alignment).0 + 1,
+            self.cigar(false)?,
+            alignment.score
+        ))
+    }
+
+    /// Trim the alignment down to its core: the longest stretch where every window of
+    /// 10 columns has an identity (`0.0..=1.0`) of at least `min_window_identity`.
+    ///
+    /// Returns the kept reference region as 0-based half-open coordinates, or `None` if no
+    /// window passes the threshold.
+    pub fn core_alignment(&self, min_window_identity: f64) -> Option<(usize, usize)> {
+        tracing::info!(
+            "Trimming alignment to core with window identity {}",
+            min_window_identity
+        );
+        let alignment = self.alignment.as_ref()?;
+        let operations = alignment
+            .operations
+            .iter()
+            .filter(|operation| {
+                !matches!(
+                    operation,
+                    AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_)
+                )
+            })
+            .collect::<Vec<_>>();
+        // Reference position right before each column, and the matches up to it
+        let mut ref_pos = vec![alignment.xstart];
+        let mut matches = vec![0];
+        for operation in &operations {
+            let consumes_ref = matches!(
+                operation,
+                AlignmentOperation::Match | AlignmentOperation::Subst | AlignmentOperation::Ins
+            );
+            ref_pos.push(ref_pos.last().unwrap() + consumes_ref as usize);
+            matches.push(
+                matches.last().unwrap() + (**operation == AlignmentOperation::Match) as usize,
+            );
+        }
+
+        let window = CORE_WINDOW.min(operations.len());
+        let passing = (0..=operations.len() - window).map(|start| {
+            window > 0
+                && (matches[start + window] - matches[start]) as f64 / window as f64
+                    >= min_window_identity
+        });
+        // Longest run of consecutive passing windows, as first and last window start
+        let mut best: Option<(usize, usize)> = None;
+        let mut run_start = None;
+        for (start, pass) in passing.enumerate() {
+            if !pass {
+                run_start = None;
+                continue;
+            }
+            let first = *run_start.get_or_insert(start);
+            if best.is_none_or(|(best_first, best_last)| start - first > best_last - best_first) {
+                best = Some((first, start));
+            }
+        }
+        best.map(|(first, last)| (ref_pos[first], ref_pos[last + window]))
+    }
+
+    /// Best score of the whole query aligned ending at each reference position, available once
+    /// [DiffStat::pairwise_aligner_semiglobal] has been run.
+    ///
+    /// The reference before the alignment is free, so this is the last row of the dynamic
+    /// programming matrix of the query against the reference: `profile[i]` is the best score
+    /// of the query aligned with its last column at reference position `i`
+    /// (`0..reference.len()`). Peaks point at the hits of the query, e.g. in a repeat.
+    pub fn semiglobal_score_profile(&self) -> Option<Vec<i32>> {
+        if self.alignment.as_ref()?.mode != AlignmentMode::Semiglobal {
+            return None;
+        }
+        tracing::info!("Calculating semiglobal score profile");
+        let (open, extend) = (self.gap_penalty.open, self.gap_penalty.extend);
+        let reference = self.reference();
+        let cols = reference.len() + 1;
+        // Low enough to never win, high enough to not overflow when a penalty is added
+        let min = i32::MIN / 2;
+
+        // Best score overall, and ending in a gap in the reference, of the previous row. The
+        // reference prefix is free, so the first row scores 0 everywhere.
+        let mut best = vec![0; cols];
+        let mut ref_gap = vec![min; cols];
+        for (j, &query_base) in self.query().iter().enumerate() {
+            let mut row_best = vec![min; cols];
+            let mut row_ref_gap = vec![min; cols];
+            // Leading part of the query can only be aligned against gaps
+            row_ref_gap[0] = open + extend * (j as i32 + 1);
+            row_best[0] = row_ref_gap[0];
+            let mut query_gap = min;
+            for (i, &ref_base) in reference.iter().enumerate() {
+                let diagonal = best[i] + self.score.score(ref_base, query_base);
+                row_ref_gap[i + 1] = (best[i + 1] + open + extend).max(ref_gap[i + 1] + extend);
+                query_gap = (row_best[i] + open + extend).max(query_gap + extend);
+                row_best[i + 1] = diagonal.max(row_ref_gap[i + 1]).max(query_gap);
+            }
+            best = row_best;
+            ref_gap = row_ref_gap;
+        }
+        // Column 0 ends before any reference base
+        Some(best.split_off(1))
+    }
+}
+
+/// Error of [DiffStatBuilder::build]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BuildError {
+    #[error("Reference sequence is not set")]
+    MissingReference,
+    #[error("Query sequence is not set")]
+    MissingQuery,
+    #[error(transparent)]
+    Gap(#[from] GapError),
+}
+
+/// Builder of a [DiffStat] scored with [Score], naming each argument of [DiffStat::new].
+/// Gap penalty and score default to [GapPanelty::default] and [Score::default].
+#[derive(Debug, Default)]
+pub struct DiffStatBuilder<'seq> {
+    reference: Option<&'seq [u8]>,
+    query: Option<&'seq [u8]>,
+    gap: Option<(i32, i32)>,
+    score: Score,
+}
+
+impl<'seq> DiffStatBuilder<'seq> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reference<T>(mut self, seq: &'seq T) -> Self
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        self.reference = Some(seq.as_ref());
+        self
+    }
+
+    pub fn query<T>(mut self, seq: &'seq T) -> Self
+    where
+        T: AsRef<[u8]> + ?Sized,
+    {
+        self.query = Some(seq.as_ref());
+        self
+    }
+
+    /// Gap penalties, both negative
+    pub fn gap(mut self, open: i32, extend: i32) -> Self {
+        self.gap = Some((open, extend));
+        self
+    }
+
+    pub fn score(mut self, r#match: i32, miss_match: i32) -> Self {
+        self.score = Score::new(r#match, miss_match);
+        self
+    }
+
+    pub fn build(self) -> Result<DiffStat<'seq, Score>, BuildError> {
+        let reference = self.reference.ok_or(BuildError::MissingReference)?;
+        let query = self.query.ok_or(BuildError::MissingQuery)?;
+        let gap_penalty = match self.gap {
+            Some((open, extend)) => GapPanelty::try_new(open, extend)?,
+            None => GapPanelty::default(),
+        };
+        Ok(DiffStat::new(reference, query, gap_penalty, self.score))
+    }
+}
+
+/// Find the reference that `query` matches best, judged by local alignment score.
+///
+/// Returns the index of the winning reference along with its score, or `None` when
+/// `references` is empty. On a tie the earliest reference wins.
+pub fn best_reference<F, S>(
+    query: &[u8],
+    references: &[S],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Option<(usize, i32)>
+where
+    F: MatchFunc + Clone + Display,
+    S: AsRef<[u8]>,
+{
+    tracing::info!(
+        "Finding best reference among {} references",
+        references.len()
+    );
+    references
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, reference)| {
+            local_alignment(query, reference.as_ref(), gap_penalty, score)
+                .map(|alignment| (idx, alignment.score))
+        })
+        .max_by(|(a_idx, a_score), (b_idx, b_score)| a_score.cmp(b_score).then(b_idx.cmp(a_idx)))
+}
+
+/// Replaces masked reference bases. It never equals a sequence byte, so it is a mismatch
+/// under [Score]; other scoring functions must score it as one for masking to hold.
+const MASK: u8 = 0;
+
+/// Best local alignment of `query` within a (possibly masked) reference
+pub(crate) fn local_alignment<F>(
+    query: &[u8],
+    reference: &[u8],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Option<PairwiseAlignment>
+where
+    F: MatchFunc + Clone + Display,
+{
+    let mut diff = DiffStat::new(
+        reference,
+        query,
+        (gap_penalty.open, gap_penalty.extend),
+        score.clone(),
+    );
+    diff.pairwise_aligner_local();
+    diff.alignment
+}
+
+/// Whether `query` aligns (locally) about as well to more than one place of the reference,
+/// e.g. a read from a repeat, making its placement ambiguous.
+///
+/// True when the best alignment after masking the reference region of the best one scores
+/// within `margin` of it.
+pub fn ambiguous_placement<F>(
+    query: &[u8],
+    reference: &[u8],
+    gap_penalty: &GapPanelty,
+    score: &F,
+    margin: i32,
+) -> bool
+where
+    F: MatchFunc + Clone + Display,
+{
+    tracing::info!("Checking for ambiguous placement with margin {}", margin);
+    let Some(best) = local_alignment(query, reference, gap_penalty, score) else {
+        return false;
+    };
+    if best.score <= 0 {
+        return false;
+    }
+    let mut masked = reference.to_vec();
+    masked[best.xstart..best.xend].fill(MASK);
+    local_alignment(query, &masked, gap_penalty, score)
+        .is_some_and(|second| second.score > 0 && best.score - second.score <= margin)
+}
+
+/// Best local alignment score for every query prefix: `best[j]` is the best score of a local
+/// alignment using only `query[..j]`
+fn best_local_prefix_scores<F>(
+    query: &[u8],
+    reference: &[u8],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Vec<i32>
+where
+    F: MatchFunc,
+{
+    let (open, extend) = (gap_penalty.open, gap_penalty.extend);
+    // Low enough to never win, high enough to not overflow when a penalty is added
+    let min = i32::MIN / 2;
+    let cols = reference.len() + 1;
+
+    let mut best = vec![0; query.len() + 1];
+    let mut prev_row = vec![0; cols];
+    let mut ref_gap = vec![min; cols];
+    for (i, &query_base) in query.iter().enumerate() {
+        let mut row = vec![0; cols];
+        let mut query_gap = min;
+        for (j, &ref_base) in reference.iter().enumerate() {
+            ref_gap[j + 1] = (prev_row[j + 1] + open + extend).max(ref_gap[j + 1] + extend);
+            query_gap = (row[j] + open + extend).max(query_gap + extend);
+            row[j + 1] = (prev_row[j] + score.score(ref_base, query_base))
+                .max(ref_gap[j + 1])
+                .max(query_gap)
+                .max(0);
+        }
+        best[i + 1] = best[i].max(row.iter().copied().max().unwrap_or(0));
+        prev_row = row;
+    }
+    best
+}
+
+/// Query split into a prefix and a suffix, each locally aligned to its own place in the reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitAlignment {
+    /// Query position where the suffix starts
+    pub split: usize,
+    /// Reference region the query prefix aligns to, 0-based half-open
+    pub prefix: Range<usize>,
+    /// Reference region the query suffix aligns to, 0-based half-open
+    pub suffix: Range<usize>,
+    /// Sum of the local alignment scores of prefix and suffix
+    pub score: i32,
+}
+
+/// Find the best way to split a query (e.g. a read spanning a breakpoint) into a prefix and
+/// a suffix that locally align to different positions of the reference.
+///
+/// Returns `None` if the query is too short to split, or either part fails to align.
+pub fn best_prefix_suffix_split<F>(
+    query: &[u8],
+    reference: &[u8],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Option<SplitAlignment>
+where
+    F: MatchFunc + Clone + Display,
+{
+    tracing::info!(
+        "Finding best split of query using {} and {}",
+        gap_penalty,
+        score
+    );
+    if query.len() < 2 {
+        return None;
+    }
+    let prefix_scores = best_local_prefix_scores(query, reference, gap_penalty, score);
+    let reversed_query = query.iter().rev().copied().collect::<Vec<_>>();
+    let reversed_reference = reference.iter().rev().copied().collect::<Vec<_>>();
+    // Best local score of each query suffix, indexed by suffix length
+    let suffix_scores =
+        best_local_prefix_scores(&reversed_query, &reversed_reference, gap_penalty, score);
+
+    let (split, prefix_score, suffix_score) = (1..query.len())
+        .map(|split| {
+            (
+                split,
+                prefix_scores[split],
+                suffix_scores[query.len() - split],
+            )
+        })
+        .max_by(
+            |(a_split, a_prefix, a_suffix), (b_split, b_prefix, b_suffix)| {
+                (a_prefix + a_suffix)
+                    .cmp(&(b_prefix + b_suffix))
+                    .then(b_split.cmp(a_split))
+            },
+        )?;
+    if prefix_score <= 0 || suffix_score <= 0 {
+        return None;
+    }
+
+    let aligned_region = |part: &[u8]| {
+        local_alignment(part, reference, gap_penalty, score)
+            .map(|alignment| alignment.xstart..alignment.xend)
+    };
+    Some(SplitAlignment {
+        split,
+        prefix: aligned_region(&query[..split])?,
+        suffix: aligned_region(&query[split..])?,
+        score: prefix_score + suffix_score,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use bio::alignment::{pairwise::MatchFunc, AlignmentOperation};
+
+    use crate::{
+        bench_support,
+        sequence::Alphabet,
+        similarity::{Hamming, Levenshtein},
+    };
+
+    use super::{
+        ambiguous_placement, best_prefix_suffix_split, best_reference, pretty_columns,
+        reverse_complement, AlignMode, AlignedRegion, AlignmentConfig, BuildError, DiffStat,
+        DiffStatBuilder, DistanceError, GapError, GapModel, GapPanelty, IllegalSymbols, IupacScore,
+        MatrixScore, Score, ScoreBreakdown, Strand, TieBreak, TooManyGaps,
+    };
+
+    #[test]
+    fn best_reference_assigns_each_query() {
+        let references = ["ACGTACGTTGCAACGTAGGC", "TTGACCGATAGGCTTACAGA"];
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        let first = best_reference(b"ACGTACGTTGCTACGTAGGC", &references, &gap, &score);
+        let second = best_reference(b"TTGACCGATCGGCTTACAGA", &references, &gap, &score);
+
+        assert_eq!(first.map(|(idx, _)| idx), Some(0));
+        assert_eq!(second.map(|(idx, _)| idx), Some(1));
+    }
+
+    #[test]
+    fn best_reference_without_references() {
+        let references: [&[u8]; 0] = [];
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+        assert_eq!(best_reference(b"ACGT", &references, &gap, &score), None);
+    }
+
+    #[test]
+    fn semiglobal_score_profile_peaks_at_read_end() {
+        let reference = bench_support::random_dna(60, 9);
+        let query = &reference[10..43];
+        let mut diffstat = DiffStat::new(&reference[..], query, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.semiglobal_score_profile(), None);
+
+        diffstat.pairwise_aligner_semiglobal();
+        let profile = diffstat
+            .semiglobal_score_profile()
+            .expect("Semiglobal alignment was performed");
+
+        assert_eq!(profile.len(), reference.len());
+        // The read ends at reference position 42, matching all of its 33 bases
+        let peak = (0..profile.len()).max_by_key(|&i| profile[i]).unwrap();
+        assert_eq!((peak, profile[peak]), (42, 33));
+        // Maximum is the score of the query aligned globally within the reference
+        let mut aligner = bio::alignment::pairwise::Aligner::new(-5, -1, Score::new(1, -1));
+        assert_eq!(profile[peak], aligner.semiglobal(query, &reference).score);
+    }
+
+    #[test]
+    fn split_read_prefix_and_suffix() {
+        let reference = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGAATTAGATCAGTTAAATGGCAGAAAACTGGCAGGGCTTTTAGTCGTG";
+        // Read spanning a breakpoint: joins reference[70..90] with reference[10..30]
+        let query = [&reference[70..90], &reference[10..30]].concat();
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        let split = best_prefix_suffix_split(&query, reference, &gap, &score)
+            .expect("Split read should align in two parts");
+
+        assert_eq!(split.split, 20);
+        assert_eq!(split.prefix, 70..90);
+        assert_eq!(split.suffix, 10..30);
+        assert_eq!(split.score, 40);
+    }
+
+    #[test]
+    fn split_needs_two_bases() {
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+        assert_eq!(best_prefix_suffix_split(b"A", b"ACGT", &gap, &score), None);
+    }
+
+    #[test]
+    fn core_alignment_trims_noisy_flanks() {
+        let core = "TTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGA";
+        let reference = format!("GGGGGGGGGG{core}GGGGGGGGGG");
+        let query = format!("AAAAAAAAAA{core}AAAAAAAAAA");
+        let mut diffstat = DiffStat::new(&reference, &query, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.core_alignment(0.95), None);
+
+        diffstat.pairwise_aligner_global();
+
+        assert_eq!(diffstat.core_alignment(0.95), Some((10, 50)));
+        assert_eq!(diffstat.core_alignment(0.0), Some((0, 60)));
+    }
+
+    #[test]
+    fn tie_break_policy() {
+        let align = |query| {
+            let mut diffstat = DiffStat::new("TTAGGAATTCAGCT", query, (-5, -1), Score::new(1, -1));
+            diffstat.pairwise_aligner_semiglobal();
+            diffstat.alignment().unwrap().clone()
+        };
+        // GAATTC is its own reverse complement, so both strands align equally well
+        let forward = align("GAATTC");
+        let reverse = align("GAATTC");
+
+        assert_eq!(
+            TieBreak::default().choose(&forward, &reverse),
+            Strand::Forward
+        );
+        assert_eq!(
+            TieBreak::PreferReverse.choose(&forward, &reverse),
+            Strand::Reverse
+        );
+        assert_eq!(
+            TieBreak::ByIdentity.choose(&forward, &reverse),
+            Strand::Forward
+        );
+        // A mismatch lowers the score, whatever the policy
+        let mismatched = align("GAGTTC");
+        assert_eq!(
+            TieBreak::PreferReverse.choose(&forward, &mismatched),
+            Strand::Forward
+        );
+    }
+
+    #[test]
+    fn best_strand_tie_break() {
+        // GAATTC is its own reverse complement, so both strands align equally well
+        let align = |tie_break| {
+            let mut diffstat =
+                DiffStat::new("TTAGGAATTCAGCT", "GAATTC", (-5, -1), Score::new(1, -1));
+            diffstat.set_tie_break(tie_break);
+            diffstat.pairwise_aligner_semiglobal_best_strand();
+            diffstat.strand()
+        };
+
+        assert_eq!(align(TieBreak::default()), Some(Strand::Forward));
+        assert_eq!(align(TieBreak::PreferForward), Some(Strand::Forward));
+        assert_eq!(align(TieBreak::PreferReverse), Some(Strand::Reverse));
+        assert_eq!(align(TieBreak::ByIdentity), Some(Strand::Forward));
+    }
+
+    #[test]
+    fn align_collapsed_homopolymers() {
+        let mut diffstat = DiffStat::new("GATTTACCAG", "GATACCCCAG", (-5, -1), Score::new(1, -1));
+        diffstat.set_collapse_homopolymers(true);
+        diffstat.pairwise_aligner_global();
+
+        assert_eq!(diffstat.reference(), b"GATACAG");
+        assert_eq!(diffstat.query(), b"GATACAG");
+        assert_eq!(
+            diffstat.alignment().map(|alignment| alignment.score),
+            Some(7)
+        );
+        // Collapsed `C` at 4 is the run starting at 6 in reference and 4 in query
+        assert_eq!(diffstat.original_reference_position(4), 6);
+        assert_eq!(diffstat.original_query_position(4), 4);
+        assert_eq!(diffstat.original_query_position(5), 8);
+    }
+
+    #[test]
+    fn placement_in_repeat_is_ambiguous() {
+        let query = "GGCAGAAAACTGGCAGGGCT";
+        let repeat = format!("CCGTAATGCCTTTCCC{query}TAACAGAGTTTTTC{query}GAACTCGTGTTG");
+        let unique = format!("CCGTAATGCCTTTCCC{query}TAACAGAGTTTTTCGAACTCGTGTTG");
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        assert!(ambiguous_placement(
+            query.as_bytes(),
+            repeat.as_bytes(),
+            &gap,
+            &score,
+            2
+        ));
+        assert!(!ambiguous_placement(
+            query.as_bytes(),
+            unique.as_bytes(),
+            &gap,
+            &score,
+            2
+        ));
+    }
+
+    #[test]
+    fn alignment_table_has_row_per_operation() {
+        let mut diffstat = DiffStat::new("ACGTTGCA", "ACGGCAA", (-5, -1), Score::new(1, -1));
+        assert!(diffstat.alignment_table().is_none());
+
+        diffstat.pairwise_aligner_global();
+        let operations = diffstat.alignment().map_or(0, |a| a.operations.len());
+        let table = diffstat.alignment_table().expect("Alignment was performed");
+
+        // Header is a row too
+        assert_eq!(table.count_rows(), operations + 1);
+        assert_eq!(table.count_columns(), 5);
+    }
+
+    #[test]
+    fn core_aligned_length_skips_end_gaps() {
+        let core = "TTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGA";
+        let reference = format!("GGGGGGGGGGGGGGG{core}GGGGGGGGGGGGGGG");
+        // Query has a 2 base deletion inside the core
+        let query = format!("{}{}", &core[..20], &core[22..]);
+        let mut diffstat = DiffStat::new(&reference, &query, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.core_aligned_length(), None);
+
+        diffstat.pairwise_aligner_semiglobal();
+
+        assert_eq!(
+            diffstat.alignment().map(|a| a.operations.len()),
+            Some(reference.len())
+        );
+        assert_eq!(diffstat.core_aligned_length(), Some(core.len()));
+    }
+
+    #[test]
+    fn gotoh_global_matches_bio() {
+        let pairs = [
+            (
+                "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+                "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            ),
+            ("CCGTCCGGCAAGGG", "AAAAACCGTTGACGGCCAA"),
+            ("ACGTTGCA", "ACGGCAA"),
+            ("TTTT", "AAAAAAAA"),
+            ("ACGT", ""),
+        ];
+        for (reference, query) in pairs {
+            for gap in [(-5, -1), (-2, -1), (-3, -2)] {
+                let mut diffstat = DiffStat::new(reference, query, gap, Score::new(1, -1));
+                diffstat.pairwise_aligner_global();
+                let gotoh = diffstat.gotoh_global();
+                let bio = diffstat.alignment().expect("Alignment was performed");
+
+                assert_eq!(gotoh.score, bio.score);
+                assert_eq!(gotoh.operations, bio.operations);
+            }
+        }
+    }
+
+    #[test]
+    fn global_alignment_with_max_gaps() {
+        let reference = "GATTACACCGTAGGCTAACGTTAGCCATGGACT";
+        let mut clean = DiffStat::new(reference, reference, (-5, -1), Score::new(1, -1));
+        assert_eq!(clean.pairwise_aligner_global_max_gaps(0), Ok(()));
+        assert!(clean.alignment().is_some());
+
+        // Deletion of TA and insertion of a T
+        let mut gapped = DiffStat::new(
+            reference,
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(
+            gapped.pairwise_aligner_global_max_gaps(1),
+            Err(TooManyGaps {
+                gaps: 2,
+                max_gaps: 1
+            })
+        );
+        assert!(gapped.alignment().is_none());
+        assert_eq!(gapped.pairwise_aligner_global_max_gaps(2), Ok(()));
+    }
+
+    #[test]
+    fn clipped_query_flanks() {
+        let reference = "GATTACACCGTAGGCTAACGTTAGCCATGGACT";
+        let query = format!("AGATCG{reference}CCCCCC");
+        let mut diffstat = DiffStat::new(reference, &query, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.clipped_sequences(), None);
+
+        diffstat.pairwise_aligner_semiglobal();
+
+        assert_eq!(
+            diffstat.clipped_sequences(),
+            Some((b"AGATCG".to_vec(), b"CCCCCC".to_vec()))
+        );
+    }
+
+    #[test]
+    fn score_breakdown_sums_to_score() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(diffstat.score_breakdown(), None);
+
+        diffstat.pairwise_aligner_global();
+        let breakdown = diffstat.score_breakdown().expect("Alignment was performed");
+
+        // 30 matches, a substitution, a 2 base deletion and a 1 base insertion
+        assert_eq!(
+            breakdown,
+            ScoreBreakdown {
+                matches: 30,
+                mismatches: -1,
+                gap_open: -10,
+                gap_extend: -3,
+            }
+        );
+        assert_eq!(
+            Some(breakdown.total()),
+            diffstat.alignment().map(|alignment| alignment.score)
+        );
+
+        diffstat.pairwise_aligner_local();
+        assert_eq!(
+            diffstat
+                .score_breakdown()
+                .map(|breakdown| breakdown.total()),
+            diffstat.alignment().map(|alignment| alignment.score)
+        );
+    }
+
+    #[test]
+    fn alignment_score_of_global_alignment() {
+        let mut diffstat = DiffStat::new("GATTACA", "GATCACA", (-5, -1), Score::new(1, -1));
+        assert!(!diffstat.is_aligned());
+        assert_eq!(diffstat.alignment_score(), None);
+
+        diffstat.pairwise_aligner_global();
+        assert!(diffstat.is_aligned());
+        assert_eq!(diffstat.alignment_score(), Some(5));
+        assert_eq!(
+            diffstat.alignment_score(),
+            diffstat.alignment().map(|alignment| alignment.score)
+        );
+    }
+
+    #[test]
+    fn percent_identity_of_pairs() {
+        let mut identical = DiffStat::new("GATTACA", "GATTACA", (-5, -1), Score::new(1, -1));
+        assert_eq!(identical.percent_identity(), None);
+        identical.pairwise_aligner_global();
+        assert_eq!(identical.percent_identity(), Some(100.0));
+
+        let mut substitution = DiffStat::new("GATTACAC", "GATCACAC", (-5, -1), Score::new(1, -1));
+        substitution.pairwise_aligner_global();
+        assert_eq!(substitution.percent_identity(), Some(87.5));
+
+        // Terminal gaps are left out
+        let mut overhang = DiffStat::new("GATTACACCG", "TTACAC", (-5, -1), Score::new(1, -1));
+        overhang.pairwise_aligner_semiglobal();
+        assert_eq!(overhang.percent_identity(), Some(100.0));
+
+        let mut empty = DiffStat::new("", "", (-5, -1), Score::new(1, -1));
+        empty.pairwise_aligner_global();
+        assert_eq!(empty.percent_identity(), Some(0.0));
+    }
+
+    #[test]
+    fn cigar_with_indels() {
+        // Query lacks the `TA` at 15..17 and has an extra `T` in `CCATGG`
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(diffstat.cigar(false), None);
+
+        diffstat.pairwise_aligner_global();
+        assert_eq!(diffstat.cigar(false).as_deref(), Some("15M2D10M1I6M"));
+
+        // Unaligned query flanks are clipped
+        let mut diffstat = DiffStat::new(
+            "CCGTAGGCTAAC",
+            "TTTCCGTAGGCTAACGG",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        diffstat.pairwise_aligner_semiglobal();
+        assert_eq!(diffstat.cigar(false).as_deref(), Some("3S12M2S"));
+        assert_eq!(diffstat.cigar(true).as_deref(), Some("3H12M2H"));
+
+        // Reference overhangs of a read in the middle of the reference are not deletions
+        let reference = bench_support::random_dna(53, 5);
+        let mut diffstat = DiffStat::new(
+            &reference[..],
+            &reference[10..43],
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        diffstat.pairwise_aligner_semiglobal();
+        assert_eq!(diffstat.cigar(false).as_deref(), Some("33M"));
+    }
+
+    #[test]
+    fn matrix_score_prefers_transitions() {
+        let nuc44 = MatrixScore::nuc44();
+        assert_eq!(nuc44.score(b'A', b'a'), 5);
+        assert_eq!(nuc44.score(b'A', b'C'), -4);
+        assert_eq!(nuc44.score(b'R', b'G'), 1);
+        assert_eq!(nuc44.score(b'N', b'T'), -2);
+
+        let bases = *b"ACGT";
+        let transitions = MatrixScore::from_pairs(bases.iter().flat_map(|a| {
+            bases.iter().map(move |b| {
+                let score = match (a, b) {
+                    (a, b) if a == b => 2,
+                    (b'A', b'G') | (b'G', b'A') | (b'C', b'T') | (b'T', b'C') => 1,
+                    _ => -4,
+                };
+                ((*a, *b), score)
+            })
+        }));
+        assert_eq!(transitions.score(b'A', b'N'), -4);
+
+        // Query against a copy with a transversion, then a copy with two transitions
+        let reference = "ACGTAAGTACTTTTTTTTTTACATACGCAC";
+        let query = "ACGTACGTAC";
+        let mut flat = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+        flat.pairwise_aligner_local();
+        let mut matrix = DiffStat::new(reference, query, (-5, -1), transitions);
+        matrix.pairwise_aligner_local();
+
+        assert_eq!(flat.alignment().map(|alignment| alignment.xstart), Some(0));
+        assert_eq!(
+            matrix.alignment().map(|alignment| alignment.xstart),
+            Some(20)
+        );
+    }
+
+    #[test]
+    fn reverse_complement_iupac() {
+        assert_eq!(reverse_complement(b"ACGTN"), b"NACGT");
+        assert_eq!(reverse_complement(b"acgRyswKMbdhv"), b"bdhvKMwsrYcgt");
+    }
+
+    #[test]
+    fn reverse_strand_outscores_forward() {
+        let reference = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAA";
+        let query = reverse_complement(&reference[10..27]);
+        let mut diffstat = DiffStat::new(&reference[..], &query[..], (-5, -1), Score::new(1, -1));
+
+        diffstat.pairwise_aligner_semiglobal();
+        let forward_score = diffstat.alignment_score();
+        diffstat.pairwise_aligner_semiglobal_best_strand();
+
+        assert_eq!(diffstat.strand(), Some(Strand::Reverse));
+        assert_eq!(diffstat.query(), &reference[10..27]);
+        assert!(forward_score < diffstat.alignment_score());
+    }
+
+    #[test]
+    fn banded_global_matches_full_global() {
+        let reference = bench_support::random_dna(2000, 7);
+        // A few substitutions, a deletion and an insertion
+        let mut query = reference.clone();
+        for pos in [150, 700, 1300] {
+            query[pos] = if query[pos] == b'A' { b'C' } else { b'A' };
+        }
+        query.drain(900..903);
+        query.insert(1600, b'T');
+
+        let mut full = DiffStat::new(&reference[..], &query[..], (-5, -1), Score::new(1, -1));
+        full.pairwise_aligner_global();
+        let mut banded = DiffStat::new(&reference[..], &query[..], (-5, -1), Score::new(1, -1));
+        banded.pairwise_aligner_banded_global(8, 10);
+
+        assert_eq!(
+            banded.alignment().map(|alignment| &alignment.operations),
+            full.alignment().map(|alignment| &alignment.operations)
+        );
+        assert_eq!(banded.alignment_score(), full.alignment_score());
+    }
+
+    #[test]
+    fn validate_dna_alphabet() {
+        let clean = DiffStat::new("GATTACA", "gattaca", (-5, -1), Score::new(1, -1));
+        assert_eq!(clean.validate_alphabet(Alphabet::Dna), Ok(()));
+
+        let dirty = DiffStat::new("GATTACA", "GATXACA", (-5, -1), Score::new(1, -1));
+        assert_eq!(
+            dirty.validate_alphabet(Alphabet::Dna),
+            Err(IllegalSymbols {
+                reference: vec![],
+                query: vec![(3, b'X')],
+            })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn summary_json_round_trips_score() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(diffstat.summary_json(), None);
+        diffstat.pairwise_aligner_global();
+
+        let summary: serde_json::Value =
+            serde_json::from_str(&diffstat.summary_json().unwrap()).unwrap();
+        assert_eq!(summary["score"], 16);
+        assert_eq!(summary["length"], 34);
+        assert_eq!(summary["mutations"]["substitution"], 1);
+        assert!(summary["percent_identity"].as_f64().unwrap() > 85.0);
+    }
+
+    #[test]
+    fn pretty_columns_of_terminal_width() {
+        assert_eq!(pretty_columns(None), 120);
+        assert_eq!(pretty_columns(Some(200)), 200);
+        assert_eq!(pretty_columns(Some(8)), 20);
+    }
+
+    #[test]
+    fn pretty_string_after_alignment() {
+        let mut diffstat = DiffStat::new("GATTACA", "GATCACA", (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.pretty_string(120), None);
+
+        diffstat.pairwise_aligner_global();
+        let pretty = diffstat.pretty_string(120).unwrap();
+        assert!(pretty.contains("GATTACA"));
+        assert!(pretty.contains("GATCACA"));
+    }
+
+    #[test]
+    fn windowed_alignment_of_identical_sequences() {
+        let reference = bench_support::random_dna(10_000, 11);
+
+        let mut diffstat =
+            DiffStat::new(&reference[..], &reference[..], (-5, -1), Score::new(1, -1));
+        diffstat.pairwise_aligner_windowed(1000, 100);
+        let alignment = diffstat.alignment().unwrap();
+        assert_eq!(alignment.operations.len(), 10_000);
+        assert!(alignment
+            .operations
+            .iter()
+            .all(|operation| *operation == AlignmentOperation::Match));
+        assert_eq!(alignment.score, 10_000);
+
+        // A deletion close to a window boundary
+        let reference = &reference[..3000];
+        let mut query = reference.to_vec();
+        query.drain(1898..1902);
+        let mut windowed = DiffStat::new(reference, &query[..], (-5, -1), Score::new(1, -1));
+        windowed.pairwise_aligner_windowed(1000, 100);
+        let mut full = DiffStat::new(reference, &query[..], (-5, -1), Score::new(1, -1));
+        full.pairwise_aligner_global();
+        assert_eq!(windowed.alignment_score(), full.alignment_score());
+    }
+
+    #[test]
+    fn align_with_linear_and_affine_gaps() {
+        let reference = b"GATTACACCGTAGGCT".as_slice();
+        let query = b"GATTACAGTAGGCT".as_slice();
+        let mut diffstat = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+
+        // 14 matches and a gap of 2
+        diffstat.align(&AlignmentConfig {
+            mode: AlignMode::Global,
+            gap_model: GapModel::Linear(-2),
+        });
+        assert_eq!(diffstat.alignment_score(), Some(14 - 2 * 2));
+
+        diffstat.align(&AlignmentConfig {
+            mode: AlignMode::Global,
+            gap_model: GapModel::from(&GapPanelty::new(-5, -1)),
+        });
+        assert_eq!(diffstat.alignment_score(), Some(14 - 5 - 2));
+        let mut affine = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+        affine.pairwise_aligner_global();
+        assert_eq!(affine.alignment_score(), diffstat.alignment_score());
+
+        // Local alignment avoids the gap, `ACCGTAGGCT` against `ACAGTAGGCT`
+        diffstat.align(&AlignmentConfig {
+            mode: AlignMode::Local,
+            gap_model: GapModel::Affine {
+                open: -5,
+                extend: -1,
+            },
+        });
+        assert_eq!(diffstat.alignment_score(), Some(9 - 1));
+    }
+
+    #[test]
+    fn gap_penalty_must_be_negative() {
+        let gap = GapPanelty::try_new(-5, -1).unwrap();
+        assert_eq!((gap.open, gap.extend), (-5, -1));
+        assert_eq!(
+            GapPanelty::try_new(0, -1).unwrap_err(),
+            GapError::NonNegativeOpen(0)
+        );
+        assert_eq!(
+            GapPanelty::try_new(-5, 2).unwrap_err(),
+            GapError::NonNegativeExtend(2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Gap open penalty must be negative")]
+    fn gap_penalty_new_panics() {
+        GapPanelty::new(1, -1);
+    }
+
+    #[test]
+    fn aligned_region_of_local_motif() {
+        let reference = b"TTGCATTGCAGATTACACCGTAGGCTTTGCA".as_slice();
+        let motif = b"GGGATTACACCGTAGGCTAA".as_slice();
+        let mut diffstat = DiffStat::new(reference, motif, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.aligned_region(), None);
+        diffstat.pairwise_aligner_local();
+
+        assert_eq!(
+            diffstat.aligned_region(),
+            Some(AlignedRegion {
+                ref_start: 10,
+                ref_end: 26,
+                query_start: 2,
+                query_end: 18,
+            })
+        );
+    }
+
+    #[test]
+    fn sam_record_fields() {
+        let reference = b"TTGCATTGCAGATTACACCGTAGGCTTTGCA".as_slice();
+        let query = b"GGGATTACACCGTAGGCTAA".as_slice();
+        let mut diffstat = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.to_sam_record("read1", "chr1"), None);
+        diffstat.pairwise_aligner_local();
+
+        let record = diffstat.to_sam_record("read1", "chr1").unwrap();
+        let fields = record.split('\t').collect::<Vec<_>>();
+        let (mandatory, tags) = fields.split_at(11);
+        assert_eq!(mandatory[0], "read1");
+        assert_eq!(mandatory[2], "chr1");
+        let ref_start = diffstat.aligned_region().unwrap().ref_start;
+        assert_eq!(mandatory[3], (ref_start + 1).to_string());
+        assert_eq!(mandatory[5], "2S16M2S");
+        assert_eq!(mandatory[9], "GGGATTACACCGTAGGCTAA");
+        assert_eq!(mandatory[10], "*");
+        assert_eq!(tags, ["AS:i:16"]);
+    }
+
+    #[test]
+    fn sam_record_of_semiglobal_read() {
+        let reference = bench_support::random_dna(53, 5);
+        let mut diffstat = DiffStat::new(
+            &reference[..],
+            &reference[10..43],
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        diffstat.pairwise_aligner_semiglobal();
+
+        let record = diffstat.to_sam_record("read1", "chr1").unwrap();
+        let fields = record.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields[3], "11");
+        assert_eq!(fields[5], "33M");
+    }
+
+    #[test]
+    fn builder_aligns_like_new() {
+        let reference = "GATTACACCGTAGGCTAACGTTAGCCATGGACT";
+        let query = "GATTACACGGTAGGCTAACGTAGCCATGGACT";
+        let mut built = DiffStatBuilder::new()
+            .reference(reference)
+            .query(query)
+            .gap(-5, -1)
+            .score(1, -1)
+            .build()
+            .unwrap();
+        let mut positional = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+        built.pairwise_aligner_global();
+        positional.pairwise_aligner_global();
+
+        assert_eq!(built.alignment(), positional.alignment());
+        assert_eq!(
+            DiffStatBuilder::new().query(query).build().unwrap_err(),
+            BuildError::MissingReference
+        );
+        assert_eq!(
+            DiffStatBuilder::new()
+                .reference(reference)
+                .build()
+                .unwrap_err(),
+            BuildError::MissingQuery
+        );
+        assert_eq!(
+            DiffStatBuilder::new()
+                .reference(reference)
+                .query(query)
+                .gap(5, -1)
+                .build()
+                .unwrap_err(),
+            BuildError::Gap(GapError::NonNegativeOpen(5))
+        );
+    }
+
+    #[test]
+    fn local_alignments_of_repeated_motif() {
+        let motif = b"GATTACACCGTAGGCTAACG";
+        let reference = [
+            bench_support::random_dna(40, 11),
+            motif.to_vec(),
+            bench_support::random_dna(50, 12),
+            motif.to_vec(),
+            bench_support::random_dna(30, 13),
+        ]
+        .concat();
+        let diffstat = DiffStat::new(&reference[..], &motif[..], (-5, -1), Score::new(1, -1));
+
+        let hits = diffstat.local_alignments(15, 5);
+        assert_eq!(hits.len(), 2);
+        let mut starts = hits.iter().map(|hit| hit.xstart).collect::<Vec<_>>();
+        starts.sort_unstable();
+        assert_eq!(starts, vec![40, 110]);
+        assert!(hits.iter().all(|hit| hit.score == 20));
+        assert_eq!(diffstat.local_alignments(15, 1).len(), 1);
+        assert!(!diffstat.is_aligned());
+    }
+
+    #[test]
+    fn windowed_progress_increases() {
+        let reference = bench_support::random_dna(2000, 3);
+        let fractions = Arc::new(Mutex::new(Vec::new()));
+        let mut diffstat =
+            DiffStat::new(&reference[..], &reference[..], (-5, -1), Score::new(1, -1));
+        let reported = fractions.clone();
+        diffstat.set_progress(Box::new(move |fraction| {
+            reported.lock().unwrap().push(fraction)
+        }));
+        diffstat.pairwise_aligner_windowed(500, 100);
+
+        let fractions = fractions.lock().unwrap();
+        assert!(fractions.len() > 1);
+        assert!(fractions.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn evolutionary_distances() {
+        // 2 transitions (T/C and C/T) and a transversion (G/C) over 20 sites
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GACTACATCGTACGCTAACG",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(diffstat.jukes_cantor_distance(), None);
+        diffstat.pairwise_aligner_global();
+
+        // -3/4 ln(1 - 4/3 * 0.15)
+        assert!((diffstat.jukes_cantor_distance().unwrap() - 0.1673577).abs() < 1e-6);
+        // -1/2 ln(1 - 2 * 0.1 - 0.05) - 1/4 ln(1 - 2 * 0.05)
+        assert!((diffstat.kimura_2p_distance().unwrap() - 0.1701812).abs() < 1e-6);
+
+        // Only transversions, beyond saturation
+        let mut saturated = DiffStat::new("ACGT", "CATG", (-5, -1), Score::new(1, -1));
+        saturated.pairwise_aligner_global();
+        assert_eq!(saturated.jukes_cantor_distance(), None);
+        assert_eq!(saturated.kimura_2p_distance(), None);
+
+        // An N is not a compared site, so it doesn't count as a transversion
+        let mut ambiguous = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GACTACATCGTACGCTAACN",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        ambiguous.pairwise_aligner_global();
+        // -1/2 ln(1 - 2 * 2/19 - 1/19) - 1/4 ln(1 - 2/19)
+        assert!((ambiguous.kimura_2p_distance().unwrap() - 0.1804972).abs() < 1e-6);
+    }
+
+    #[test]
+    fn distance_with_metric() {
+        let diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GACTACATCGTACGCTAACG",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+
+        assert_eq!(diffstat.distance(Hamming), diffstat.hamming_distance());
+        assert_eq!(diffstat.distance(Hamming), 3);
+        assert_eq!(
+            diffstat.distance(Levenshtein),
+            diffstat.levenshtein() as u64
+        );
+    }
+
+    #[test]
+    fn hamming_distance_of_unequal_lengths() {
+        let diffstat = DiffStat::new("GATTACA", "GATTAC", (-5, -1), Score::new(1, -1));
+        let err = diffstat.try_hamming_distance().unwrap_err();
+
+        assert_eq!(
+            err,
+            DistanceError::UnequalLengths {
+                reference: 7,
+                query: 6
+            }
+        );
+        assert!(err
+            .to_string()
+            .contains("reference has 7 bases and query 6"));
+        let same = DiffStat::new("GATTACA", "GACTACA", (-5, -1), Score::new(1, -1));
+        assert_eq!(same.try_hamming_distance(), Ok(1));
+    }
+
+    #[test]
+    fn html_marks_substitution() {
+        let mut diffstat = DiffStat::new("GATTACA", "GACTACA", (-5, -1), Score::new(1, -1));
+        assert_eq!(diffstat.to_html(80), None);
+        diffstat.pairwise_aligner_global();
+
+        let html = diffstat.to_html(80).unwrap();
+        let rows = html.lines().collect::<Vec<_>>();
+        assert_eq!(
+            rows[1],
+            concat!(
+                r#"<span class="match" style="color: gray">GA</span>"#,
+                r#"<span class="subst" style="color: red">T</span>"#,
+                r#"<span class="match" style="color: gray">TACA</span>"#
+            )
+        );
+        assert!(rows[2].contains(r#"<span class="subst" style="color: red">C</span>"#));
+        assert_eq!(html.matches(r#"class="subst""#).count(), 2);
+        // Wrapped into 3 blocks of reference, query and a blank line
+        assert_eq!(diffstat.to_html(3).unwrap().lines().count(), 2 + 3 * 3);
+    }
+
+    #[test]
+    fn iupac_partial_matches() {
+        let score = IupacScore::new(2, 1, -1);
+
+        assert_eq!(score.score(b'A', b'a'), 2);
+        assert_eq!(score.score(b'A', b'N'), 1);
+        assert_eq!(score.score(b'R', b'a'), 1);
+        assert_eq!(score.score(b'R', b'S'), 1);
+        assert_eq!(score.score(b'A', b'Y'), -1);
+        assert_eq!(score.score(b'A', b'-'), -1);
+    }
+
+    #[test]
+    fn operation_runs_group_gap() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GATTACACCGTTTTAGGCTAACG",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        assert_eq!(diffstat.operation_runs(), None);
+        diffstat.pairwise_aligner_global();
+
+        // The 3 extra query bases are bio's `Del`
+        let runs = diffstat.operation_runs().unwrap();
+        assert!(runs.contains(&(AlignmentOperation::Del, 3)));
+        assert_eq!(runs.iter().map(|(_, count)| count).sum::<usize>(), 23);
+        assert_eq!(runs.len(), 3);
+    }
+
+    /// Log sink shared with the test, as the subscriber needs a writer per event
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn warn_on_swapped_lengths() {
+        let reference = bench_support::random_dna(50, 7);
+        let query = bench_support::random_dna(1000, 8);
+        let swapped = DiffStat::new(&reference, &query, (-5, -1), Score::new(1, -1));
+        let fine = DiffStat::new(&query, &reference, (-5, -1), Score::new(1, -1));
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(swapped.warn_if_suspicious());
+            assert!(!fine.warn_if_suspicious());
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.lines().count(), 1);
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("Query (1000 bp) is more than 10x longer than the reference (50 bp)"));
+    }
 }
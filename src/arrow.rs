@@ -0,0 +1,107 @@
+//! Write batch results as [Parquet](https://parquet.apache.org/), to be loaded by data-science
+//! tools like pandas or polars. Requires the `arrow` feature.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+
+use crate::mutation_detection::{MutationReport, MutationStats};
+
+/// Schema of the Parquet file, one row per [MutationReport]
+pub fn report_schema() -> Schema {
+    let count = |name| Field::new(name, DataType::UInt64, false);
+    Schema::new(vec![
+        Field::new("query", DataType::Utf8, false),
+        Field::new("reference", DataType::Utf8, false),
+        count("match"),
+        count("miss_match"),
+        count("substitution"),
+        count("insertions"),
+        count("deletions"),
+        count("total"),
+    ])
+}
+
+/// Write `reports` into a Parquet file at `path`, replacing it if it exists
+pub fn write_parquet<P>(path: P, reports: &[MutationReport]) -> Result<(), ParquetError>
+where
+    P: AsRef<Path>,
+{
+    tracing::info!(
+        "Writing {} reports to parquet file {:?}",
+        reports.len(),
+        path.as_ref()
+    );
+    let strings = |field: fn(&MutationReport) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(reports.iter().map(field)))
+    };
+    let counts = |field: fn(&MutationStats) -> usize| -> ArrayRef {
+        Arc::new(
+            reports
+                .iter()
+                .map(|report| field(report.stats()) as u64)
+                .collect::<UInt64Array>(),
+        )
+    };
+    let schema = Arc::new(report_schema());
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            strings(MutationReport::query),
+            strings(MutationReport::reference),
+            counts(MutationStats::r#match),
+            counts(MutationStats::miss_match),
+            counts(MutationStats::substitution),
+            counts(MutationStats::insertions),
+            counts(MutationStats::deletions),
+            counts(MutationStats::total),
+        ],
+    )?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::{report_schema, write_parquet};
+    use crate::mutation_detection::{MutationReport, MutationStats};
+
+    #[test]
+    fn parquet_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "dna_sequence_analysis_{}_reports.parquet",
+            std::process::id()
+        ));
+        let mut stats = MutationStats::default();
+        stats.inc_match();
+        let reports = vec![
+            MutationReport::new("query1", "reference1", stats),
+            MutationReport::new("query2", "reference1", MutationStats::default()),
+        ];
+
+        write_parquet(&path, &reports).expect("Unable to write parquet");
+        let builder = ParquetRecordBatchReaderBuilder::try_new(
+            File::open(&path).expect("Parquet file is missing"),
+        )
+        .expect("Unable to read parquet");
+        let schema = builder.schema().clone();
+        let rows = builder
+            .build()
+            .expect("Unable to read parquet")
+            .map(|batch| batch.expect("Unable to read record batch").num_rows())
+            .sum::<usize>();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(schema.fields(), report_schema().fields());
+        assert_eq!(rows, 2);
+    }
+}
@@ -0,0 +1,177 @@
+//! Aligning many queries against one reference in parallel
+
+use std::fmt::Display;
+
+use bio::alignment::pairwise::MatchFunc;
+use rayon::{prelude::*, ThreadPoolBuilder};
+
+use crate::{
+    aliner::{DiffStat, GapPanelty, Score},
+    mutation_detection::{Muatation, MutationStats},
+};
+
+/// Thread pool builder with `n` threads, `0` meaning one per available core
+fn thread_pool_builder(n: usize) -> ThreadPoolBuilder {
+    ThreadPoolBuilder::new().num_threads(n)
+}
+
+/// Configure the global thread pool used by the parallel functions of this crate to run on `n`
+/// threads, or on all cores if `n` is `0`.
+///
+/// Must be called before any parallel work, the global pool can only be configured once.
+pub fn configure_thread_pool(n: usize) -> anyhow::Result<()> {
+    tracing::info!("Configuring thread pool with {n} threads");
+    thread_pool_builder(n).build_global()?;
+    Ok(())
+}
+
+/// Globally align every `(id, sequence)` query against `reference` in parallel, returning the
+/// [MutationStats] of each query tagged with its id.
+///
+/// Results are in the order of `queries`, whatever the thread scheduling.
+pub fn align_batch<F>(
+    reference: &[u8],
+    queries: &[(String, Vec<u8>)],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Vec<(String, MutationStats)>
+where
+    F: MatchFunc + Clone + Display + Sync,
+{
+    tracing::info!("Aligning batch of {} queries", queries.len());
+    queries
+        .par_iter()
+        .map(|(id, query)| {
+            let mut diff = DiffStat::new(
+                reference,
+                query.as_slice(),
+                (gap_penalty.open, gap_penalty.extend),
+                score.clone(),
+            );
+            diff.pairwise_aligner_global();
+            let stats = Muatation::from(&diff)
+                .mutation_score()
+                .expect("Alignment was performed");
+            (id.clone(), stats)
+        })
+        .collect()
+}
+
+/// [MutationStats] of every pair of `(id, sequence)`, each unordered pair aligned once
+/// (semiglobal) in parallel with the lower index as reference.
+///
+/// The matrix is symmetric, `[j][i]` being a copy of `[i][j]`, and its diagonal counts every
+/// base of a sequence as a match.
+pub fn all_pairs_mutation_matrix(
+    seqs: &[(String, Vec<u8>)],
+    gap: GapPanelty,
+    score: Score,
+) -> Vec<Vec<MutationStats>> {
+    tracing::info!("Aligning all pairs of {} sequences", seqs.len());
+    let pairs = (0..seqs.len())
+        .flat_map(|i| (i + 1..seqs.len()).map(move |j| (i, j)))
+        .collect::<Vec<_>>();
+    let stats = pairs
+        .par_iter()
+        .map(|&(i, j)| {
+            let mut diff = DiffStat::new(seqs[i].1.as_slice(), seqs[j].1.as_slice(), gap, score);
+            diff.pairwise_aligner_semiglobal();
+            Muatation::from(&diff)
+                .mutation_score()
+                .expect("Alignment was performed")
+        })
+        .collect::<Vec<_>>();
+
+    let mut matrix = seqs
+        .iter()
+        .map(|(_, seq)| {
+            let mut identical = MutationStats::default();
+            seq.iter().for_each(|_| identical.inc_match());
+            vec![identical; seqs.len()]
+        })
+        .collect::<Vec<_>>();
+    for ((i, j), stats) in pairs.into_iter().zip(stats) {
+        matrix[j][i] = stats;
+        matrix[i][j] = stats;
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::{GapPanelty, Score};
+
+    use super::{align_batch, all_pairs_mutation_matrix, thread_pool_builder};
+
+    #[test]
+    fn align_batch_keeps_input_order() {
+        let reference = b"GATTACACCGTAGGCTAACGTTAGCCATGGACT";
+        let queries = (0..64)
+            .map(|i| {
+                let mut query = reference.to_vec();
+                query.truncate(reference.len() - i % 8);
+                let pos = i % query.len();
+                query[pos] = b'A';
+                (format!("query{i}"), query)
+            })
+            .collect::<Vec<_>>();
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        let first = format!("{:?}", align_batch(reference, &queries, &gap, &score));
+        let second = format!("{:?}", align_batch(reference, &queries, &gap, &score));
+
+        assert_eq!(first, second);
+        let ids = align_batch(reference, &queries, &gap, &score)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            queries.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn all_pairs_matrix_is_symmetric() {
+        let seqs = ["GATTACACCGTAGGCT", "GATTACACCGTTGGCT", "GATTCCACCGTAGGCA"]
+            .iter()
+            .enumerate()
+            .map(|(i, seq)| (format!("seq{i}"), seq.as_bytes().to_vec()))
+            .collect::<Vec<_>>();
+
+        let matrix = all_pairs_mutation_matrix(&seqs, GapPanelty::new(-5, -1), Score::new(1, -1));
+
+        assert_eq!(matrix.len(), 3);
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row[i].r#match(), 16);
+            assert_eq!(row[i].miss_match(), 0);
+            for (j, stats) in row.iter().enumerate() {
+                assert_eq!(*stats, matrix[j][i]);
+            }
+        }
+        assert_eq!(matrix[0][1].substitution(), 1);
+        assert_eq!(matrix[0][2].substitution(), 2);
+    }
+
+    #[test]
+    fn align_batch_in_two_threads() {
+        let reference = b"GATTACACCGTAGGCTAACGTTAGCCATGGACT";
+        let queries = (0..16)
+            .map(|i| {
+                let mut query = reference.to_vec();
+                query[i] = b'A';
+                (format!("query{i}"), query)
+            })
+            .collect::<Vec<_>>();
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        let pool = thread_pool_builder(2).build().unwrap();
+        assert_eq!(pool.current_num_threads(), 2);
+        let in_pool = pool.install(|| align_batch(reference, &queries, &gap, &score));
+
+        let expected = align_batch(reference, &queries, &gap, &score);
+        assert_eq!(format!("{in_pool:?}"), format!("{expected:?}"));
+    }
+}
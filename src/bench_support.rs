@@ -0,0 +1,46 @@
+//! Reproducible inputs shared by the benchmarks, the tests and random sampling of scores
+
+/// Deterministic pseudo random DNA of `len` bases, the same for a given `seed`, so benchmarks
+/// are comparable across runs without a rng dependency
+pub fn random_dna(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(seed >> 62) as usize]
+        })
+        .collect()
+}
+
+/// Copy of `seq` with every `every`-th base substituted, a similar sequence to align against
+pub fn mutate(seq: &[u8], every: usize) -> Vec<u8> {
+    seq.iter()
+        .enumerate()
+        .map(
+            |(pos, base)| match (every > 0 && pos % every == every - 1, base) {
+                (true, b'A') => b'C',
+                (true, _) => b'A',
+                (false, _) => *base,
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mutate, random_dna};
+
+    #[test]
+    fn random_dna_is_deterministic() {
+        let dna = random_dna(1000, 42);
+
+        assert_eq!(dna, random_dna(1000, 42));
+        assert_ne!(dna, random_dna(1000, 43));
+        assert_eq!(dna.len(), 1000);
+        assert!(dna.iter().all(|base| b"ACGT".contains(base)));
+        let mutated = mutate(&dna, 100);
+        let differences = dna.iter().zip(&mutated).filter(|(a, b)| a != b).count();
+        assert_eq!(differences, 10);
+    }
+}
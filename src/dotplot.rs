@@ -0,0 +1,159 @@
+//! Dotplots of shared k-mers, showing repeats and rearrangements that a linear alignment hides
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::aliner::{reverse_complement, Strand};
+
+/// Coordinates `(i, j)` of every k-mer shared by `a` (starting at `i`) and `b` (starting at
+/// `j`), sorted. Empty if `k` is `0`.
+pub fn dotplot(a: &[u8], b: &[u8], k: usize) -> Vec<(usize, usize)> {
+    tracing::info!("Building dotplot with k {}", k);
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut kmers: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, kmer) in a.windows(k).enumerate() {
+        kmers.entry(kmer).or_default().push(i);
+    }
+    let mut points = b
+        .windows(k)
+        .enumerate()
+        .flat_map(|(j, kmer)| kmers.get(kmer).into_iter().flatten().map(move |&i| (i, j)))
+        .collect::<Vec<_>>();
+    points.sort_unstable();
+    points
+}
+
+/// Render dotplot points as `height` lines of `width` characters, `a` along the x axis and `b`
+/// down the y axis, both scaled to the largest coordinate. A cell holding any point is drawn
+/// as `*`, an empty one as `.`
+pub fn dotplot_ascii(points: &[(usize, usize)], width: usize, height: usize) -> String {
+    let a_len = points.iter().map(|(i, _)| i + 1).max().unwrap_or(1);
+    let b_len = points.iter().map(|(_, j)| j + 1).max().unwrap_or(1);
+    let mut grid = vec![vec![b'.'; width]; height];
+    if width > 0 && height > 0 {
+        for &(i, j) in points {
+            grid[j * height / b_len][i * width / a_len] = b'*';
+        }
+    }
+    grid.into_iter()
+        .map(|row| String::from_utf8(row).expect("Grid is ASCII") + "\n")
+        .collect()
+}
+
+/// Region conserved between two sequences, `b` being reverse complemented for an inverted block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntenyBlock {
+    pub a: Range<usize>,
+    pub b: Range<usize>,
+    pub strand: Strand,
+}
+
+/// Chain dotplot points lying on the same diagonal, at most `word` apart, into
+/// `(a_range, b_range)` blocks
+fn chain_diagonals(points: &[(usize, usize)], word: usize) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut diagonals: HashMap<isize, Vec<usize>> = HashMap::new();
+    for &(i, j) in points {
+        diagonals
+            .entry(j as isize - i as isize)
+            .or_default()
+            .push(i);
+    }
+    diagonals
+        .into_iter()
+        .flat_map(|(diagonal, starts)| {
+            // Points are sorted, so starts on a diagonal are too
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for i in starts {
+                match runs.last_mut() {
+                    Some((_, last)) if i - *last <= word => *last = i,
+                    _ => runs.push((i, i)),
+                }
+            }
+            runs.into_iter().map(move |(first, last)| {
+                let j = (first as isize + diagonal) as usize;
+                (first..last + word, j..j + last - first + word)
+            })
+        })
+        .collect()
+}
+
+/// Synteny blocks of `a` and `b`: runs of `word` long matches chained along a diagonal of the
+/// dotplot, on both strands of `b`. Blocks shorter than `min_block` bases are dropped.
+///
+/// Returns the blocks sorted by their start in `a`, with coordinates as 0-based half-open
+/// ranges on the forward strand of both sequences.
+pub fn synteny_blocks(a: &[u8], b: &[u8], word: usize, min_block: usize) -> Vec<SyntenyBlock> {
+    tracing::info!("Finding synteny blocks with word {}", word);
+    let forward = chain_diagonals(&dotplot(a, b, word), word)
+        .into_iter()
+        .map(|(a, b)| SyntenyBlock {
+            a,
+            b,
+            strand: Strand::Forward,
+        });
+    let reverse = chain_diagonals(&dotplot(a, &reverse_complement(b), word), word)
+        .into_iter()
+        .map(|(a, rc)| SyntenyBlock {
+            a,
+            b: b.len() - rc.end..b.len() - rc.start,
+            strand: Strand::Reverse,
+        });
+    let mut blocks = forward
+        .chain(reverse)
+        .filter(|block| block.a.len() >= min_block)
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|block| (block.a.start, block.b.start));
+    blocks
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        aliner::{reverse_complement, Strand},
+        bench_support::random_dna,
+    };
+
+    use super::{dotplot, dotplot_ascii, synteny_blocks, SyntenyBlock};
+
+    #[test]
+    fn collinear_and_inverted_blocks() {
+        let first = random_dna(60, 7);
+        let second = random_dna(60, 42);
+        // Differing bases at the junction, so the blocks cant extend over it by chance
+        let a = [first.as_slice(), b"G", &second].concat();
+        let b = [first.as_slice(), b"C", &reverse_complement(&second)].concat();
+
+        assert_eq!(
+            synteny_blocks(&a, &b, 8, 30),
+            vec![
+                SyntenyBlock {
+                    a: 0..60,
+                    b: 0..60,
+                    strand: Strand::Forward,
+                },
+                SyntenyBlock {
+                    a: 61..121,
+                    b: 61..121,
+                    strand: Strand::Reverse,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_sequences_on_diagonal() {
+        let seq = b"ACGTTGCAAGGCTTACCGAT";
+        let points = dotplot(seq, seq, 4);
+
+        assert_eq!(points, (0..17).map(|i| (i, i)).collect::<Vec<_>>());
+        let plot = dotplot_ascii(&points, 17, 17);
+        for (row, line) in plot.lines().enumerate() {
+            let expected = (0..17)
+                .map(|column| if column == row { '*' } else { '.' })
+                .collect::<String>();
+            assert_eq!(line, expected);
+        }
+        assert_eq!(dotplot_ascii(&points, 4, 2), "**..\n..**\n");
+    }
+}
@@ -1,10 +1,30 @@
 pub mod aliner;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod batch;
+pub mod bench_support;
+pub mod dotplot;
+pub mod minimizers;
+pub mod msa;
 pub mod mutation_detection;
+pub mod output;
+pub mod phylogeny;
+pub mod pileup;
+pub mod polish;
+pub mod quantiles;
 pub mod reader;
+pub mod sequence;
+pub mod significance;
+pub mod similarity;
+pub mod translation;
 
-/// Initiate tracing
+/// Initiate tracing of warnings and errors to stderr, keeping stdout for the results. Logs
+/// are written until the returned guard is dropped.
 pub fn init_logging() -> tracing_appender::non_blocking::WorkerGuard {
-    let (non_blocking, guard) = tracing_appender::non_blocking(std::io::stdout());
-    tracing_subscriber::fmt().with_writer(non_blocking).init();
+    let (non_blocking, guard) = tracing_appender::non_blocking(std::io::stderr());
+    tracing_subscriber::fmt()
+        .with_writer(non_blocking)
+        .with_max_level(tracing::Level::WARN)
+        .init();
     guard
 }
@@ -1,69 +1,401 @@
-use clap::{arg, Parser};
+use anyhow::anyhow;
+use bio::{alignment::pairwise::MatchFunc, io::fasta::Record};
+use clap::{Parser, ValueEnum};
 use dna_sequence_analysis::{
-    aliner::{DiffStat, GapPanelty, Score},
+    aliner::{best_reference, AlignMode, AlignmentConfig, DiffStat, GapModel, GapPanelty, Score},
+    batch::configure_thread_pool,
     init_logging,
-    mutation_detection::Muatation,
-    reader::FastaReader,
+    mutation_detection::{Muatation, MutationReport},
+    output,
+    quantiles::IdentityQuantiles,
+    reader::{FastaReader, RecordExt},
 };
-use std::{path::PathBuf, time::Instant};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::{fmt::Display, io, path::PathBuf, time::Instant};
+use tabled::Table;
+
+/// Resolution of the alignment progress bar
+const PROGRESS_STEPS: u64 = 1000;
 
 enum _CliError {
     Generic(String),
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+    Tsv,
+    /// Variant records, not available in batch mode
+    Vcf,
+    Sam,
+}
+
 #[derive(Parser, Debug)]
 struct Cli {
     /// Reference (master) FASTA file
     #[arg(short, long, value_name = "FILE")]
     reference: PathBuf,
 
-    /// Query FASTA file, the sequence which will be aligned
-    #[arg(short, long, value_name = "FILE")]
-    query: PathBuf,
+    /// Query FASTA file, the sequence which will be aligned. Repeat it to align several query
+    /// files, reported in one table as in batch mode
+    #[arg(short, long, value_name = "FILE", required = true)]
+    query: Vec<PathBuf>,
 
     /// Print the alignemnt
     #[arg(short, long)]
     print: bool,
+
+    /// Align every query record against its best matching record of the reference file
+    #[arg(short, long)]
+    batch: bool,
+
+    /// Report only structural differences (insertions and deletions), ignoring substitutions.
+    /// Only applies to the table format, not available in batch mode
+    #[arg(long)]
+    sv_only: bool,
+
+    /// Report only the first N differences (substitutions, insertions and deletions) in
+    /// the table format, for a quick look at large sequences. Not available in batch mode
+    #[arg(long, value_name = "N", conflicts_with = "sv_only")]
+    max_diffs: Option<usize>,
+
+    /// Print lengths and checksums of the aligned records, warning when the reference and
+    /// query look like the same record (e.g. the same file was passed twice)
+    #[arg(long)]
+    verify_pair: bool,
+
+    /// Fail instead of warning when the inputs look swapped, i.e. the query is much longer
+    /// than the reference
+    #[arg(long)]
+    strict: bool,
+
+    /// Alignment of the query against the reference
+    #[arg(short, long, value_enum, default_value_t)]
+    mode: AlignMode,
+
+    /// Score of a match
+    #[arg(
+        long = "match",
+        value_name = "SCORE",
+        default_value_t = 1,
+        allow_negative_numbers = true
+    )]
+    match_score: i32,
+
+    /// Score of a mismatch
+    #[arg(long, default_value_t = -1, allow_negative_numbers = true)]
+    mismatch: i32,
+
+    /// Gap open penalty, negative
+    #[arg(long, default_value_t = -5, allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(..0))]
+    gap_open: i32,
+
+    /// Gap extend penalty, negative
+    #[arg(long, default_value_t = -1, allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(..0))]
+    gap_extend: i32,
+
+    /// Output format of the results
+    #[arg(short, long, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    /// Print a JSON summary of the alignment (score, identity, length and mutations) instead
+    /// of the table
+    #[cfg(feature = "serde")]
+    #[arg(long, conflicts_with_all = ["batch", "format", "sv_only", "max_diffs"])]
+    json: bool,
+
+    /// Show a progress bar of the alignment, or of the queries in batch mode
+    #[arg(long)]
+    progress: bool,
+
+    /// Number of threads aligning the queries of batch mode in parallel, all cores if 0
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+
+    /// Also write the batch results into a Parquet file
+    #[cfg(feature = "arrow")]
+    #[arg(long, value_name = "FILE", requires = "batch")]
+    parquet: Option<PathBuf>,
 }
 
-fn find_mutation(reference: PathBuf, query: PathBuf, print: bool) -> anyhow::Result<()> {
-    let reference = FastaReader::from_file(reference)?;
-    let query = FastaReader::from_file(query)?;
+/// Print lengths and checksums of a reference and query pair to stderr, and log a warning when
+/// they have the same ID
+fn verify_pair(reference: &Record, query: &Record) {
+    for (kind, record) in [("reference", reference), ("query", query)] {
+        eprintln!(
+            "{kind} {}: {} bp, sha256 {}",
+            record.id(),
+            record.seq().len(),
+            record.sequence_hash()
+        );
+    }
+    if reference.id() == query.id() {
+        tracing::warn!(
+            "Reference and query have the same ID `{}`, was the same file passed twice?",
+            reference.id()
+        );
+    }
+}
+
+/// Warn when the reference and query look swapped, or fail with `strict`
+fn check_suspicious<F>(diff: &DiffStat<F>, strict: bool) -> anyhow::Result<()>
+where
+    F: MatchFunc + Clone + Display,
+{
+    if diff.warn_if_suspicious() && strict {
+        return Err(anyhow!(
+            "Query is much longer than the reference, were they swapped?"
+        ));
+    }
+    Ok(())
+}
+
+/// Write reports in one of the formats that only need the mutation statistics
+fn write_reports(format: OutputFormat, reports: &[MutationReport]) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => println!("Score: \n{}", Table::new(reports)),
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => output::write_json(io::stdout(), reports)?,
+        #[cfg(not(feature = "serde"))]
+        OutputFormat::Json => return Err(anyhow!("JSON output requires the serde feature")),
+        OutputFormat::Csv => output::write_delimited(io::stdout(), reports, b',')?,
+        OutputFormat::Tsv => output::write_delimited(io::stdout(), reports, b'\t')?,
+        OutputFormat::Vcf | OutputFormat::Sam => {
+            return Err(anyhow!(
+                "{format:?} output needs the alignments, not only reports"
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn find_mutation(args: &Cli) -> anyhow::Result<()> {
+    let reference = FastaReader::from_file(&args.reference)?;
+    let query = FastaReader::from_file(&args.query[0])?;
 
     // FASTA files contain only 1 sequence
-    let reference_record = reference.records().next().unwrap()?;
+    let reference_record = reference.single_record()?;
     let reference_seq = reference_record.seq();
-    let query_record = query.records().next().unwrap()?;
+    let query_record = query.single_record()?;
     let query_seq = query_record.seq();
+    if args.verify_pair {
+        verify_pair(&reference_record, &query_record);
+    }
 
-    // Default score
-    let score = Score::new(1, -1);
-    let gap = GapPanelty::new(-5, -1);
+    let score = Score::new(args.match_score, args.mismatch);
+    let gap = GapPanelty::try_new(args.gap_open, args.gap_extend)?;
+    let config = AlignmentConfig {
+        mode: args.mode,
+        gap_model: GapModel::from(&gap),
+    };
 
     let mut diff = DiffStat::new(reference_seq, query_seq, gap, score);
+    check_suspicious(&diff, args.strict)?;
+    let progress = args.progress.then(|| ProgressBar::new(PROGRESS_STEPS));
+    if let Some(bar) = progress.clone() {
+        diff.set_progress(Box::new(move |fraction| {
+            bar.set_position((fraction * PROGRESS_STEPS as f32) as u64)
+        }));
+    }
 
     let time = Instant::now();
-    diff.pairwise_aligner_global();
+    diff.align(&config);
+    if let Some(bar) = progress {
+        bar.finish_and_clear();
+    }
 
-    print.then(|| diff.pretty_print(120));
+    args.print.then(|| diff.pretty_print_auto());
 
     let ms = Muatation::from(&diff);
 
-    println!("Score: \n{}", ms.mutation_score().unwrap());
-    println!("time taken: {:?}", time.elapsed());
+    #[cfg(feature = "serde")]
+    if args.json {
+        println!("{}", diff.summary_json().unwrap());
+        return Ok(());
+    }
+    match args.format {
+        OutputFormat::Table if args.sv_only => println!(
+            "Structural differences: \n{}",
+            Table::new(ms.structural_only())
+        ),
+        OutputFormat::Table => match args.max_diffs {
+            Some(max_diffs) => println!(
+                "First {max_diffs} differences: \n{}",
+                Table::new(ms.first_n_mutations(max_diffs))
+            ),
+            None => println!("Score: \n{}", ms.mutation_score().unwrap()),
+        },
+        OutputFormat::Vcf => ms.to_vcf(io::stdout(), reference_record.id())?,
+        OutputFormat::Sam => {
+            println!("@HD\tVN:1.6");
+            println!(
+                "@SQ\tSN:{}\tLN:{}",
+                reference_record.id(),
+                reference_seq.len()
+            );
+            println!(
+                "{}",
+                diff.to_sam_record(query_record.id(), reference_record.id())
+                    .unwrap()
+            );
+        }
+        format => write_reports(
+            format,
+            &[MutationReport::new(
+                query_record.id(),
+                reference_record.id(),
+                ms.mutation_score().unwrap(),
+            )],
+        )?,
+    }
+    if args.format == OutputFormat::Table {
+        println!("time taken: {:?}", time.elapsed());
+    }
+
+    Ok(())
+}
+
+fn batch_mutation(args: &Cli) -> anyhow::Result<()> {
+    if args.format == OutputFormat::Vcf {
+        return Err(anyhow!("VCF output is not available in batch mode"));
+    }
+    if args.sv_only || args.max_diffs.is_some() {
+        return Err(anyhow!(
+            "--sv-only and --max-diffs are not available in batch mode"
+        ));
+    }
+    let references = FastaReader::from_file(&args.reference)?.all_records()?;
+    let queries = args
+        .query
+        .iter()
+        .map(|path| FastaReader::from_file(path)?.all_records())
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+    let reference_seqs = references
+        .iter()
+        .map(|record| record.seq())
+        .collect::<Vec<_>>();
+
+    let score = Score::new(args.match_score, args.mismatch);
+    let gap = GapPanelty::try_new(args.gap_open, args.gap_extend)?;
+    let config = AlignmentConfig {
+        mode: args.mode,
+        gap_model: GapModel::from(&gap),
+    };
+
+    let time = Instant::now();
+    let progress = args
+        .progress
+        .then(|| ProgressBar::new(queries.len() as u64));
+    // Queries are aligned in parallel, anything printed along is kept for after, in order
+    let aligned = queries
+        .par_iter()
+        .map(|query_record| {
+            let (idx, _) = best_reference(query_record.seq(), &reference_seqs, &gap, &score)
+                .ok_or_else(|| anyhow!("Reference file does not contain any record"))?;
+            let reference_record = &references[idx];
+
+            let mut diff = DiffStat::new(
+                reference_record.seq(),
+                query_record.seq(),
+                (gap.open, gap.extend),
+                score,
+            );
+            check_suspicious(&diff, args.strict)?;
+            diff.align(&config);
+
+            let pretty = args.print.then(|| diff.pretty_string_auto()).flatten();
+            let ms = Muatation::from(&diff);
+            let stats = ms.mutation_score().unwrap();
+            let sam_record = (args.format == OutputFormat::Sam)
+                .then(|| diff.to_sam_record(query_record.id(), reference_record.id()))
+                .flatten();
+            if let Some(bar) = &progress {
+                bar.inc(1);
+            }
+            Ok((
+                reference_record,
+                pretty,
+                ms.strict_identity(),
+                MutationReport::new(query_record.id(), reference_record.id(), stats),
+                sam_record,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(bar) = progress {
+        bar.finish_and_clear();
+    }
+
+    let mut identities = IdentityQuantiles::new();
+    let mut reports = Vec::with_capacity(aligned.len());
+    let mut sam_records = Vec::with_capacity(aligned.len());
+    for (query_record, (reference_record, pretty, identity, report, sam_record)) in
+        queries.iter().zip(aligned)
+    {
+        if args.verify_pair {
+            verify_pair(reference_record, query_record);
+        }
+        if let Some(pretty) = pretty {
+            println!("{pretty}");
+        }
+        if let Some(identity) = identity {
+            identities.add(identity);
+        }
+        reports.push(report);
+        sam_records.push(sam_record);
+    }
+
+    if args.format == OutputFormat::Sam {
+        println!("@HD\tVN:1.6");
+        for record in &references {
+            println!("@SQ\tSN:{}\tLN:{}", record.id(), record.seq().len());
+        }
+        sam_records
+            .iter()
+            .flatten()
+            .for_each(|record| println!("{record}"));
+    } else {
+        write_reports(args.format, &reports)?;
+    }
+    if args.format == OutputFormat::Table {
+        println!(
+            "identity median: {:.4}, p90: {:.4}",
+            identities.quantile(0.5),
+            identities.quantile(0.9)
+        );
+        println!("time taken: {:?}", time.elapsed());
+    }
+
+    #[cfg(feature = "arrow")]
+    if let Some(path) = &args.parquet {
+        dna_sequence_analysis::arrow::write_parquet(path, &reports)?;
+    }
 
     Ok(())
 }
 
 fn run_cli() -> anyhow::Result<()> {
     let args = Cli::parse();
+    configure_thread_pool(args.threads)?;
 
-    find_mutation(args.reference, args.query, args.print)?;
+    if args.batch || args.query.len() > 1 {
+        #[cfg(feature = "serde")]
+        if args.json {
+            return Err(anyhow!("--json needs a single query file"));
+        }
+        batch_mutation(&args)?;
+    } else {
+        find_mutation(&args)?;
+    }
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    let _ = init_logging();
+    let _guard = init_logging();
     run_cli()?;
 
     Ok(())
@@ -0,0 +1,82 @@
+//! [Minimizer](https://doi.org/10.1093/bioinformatics/bth408) sketches of sequences, a small
+//! sample of k-mers that similar sequences are likely to share. Used to find candidate
+//! overlaps between reads before running a costly alignment.
+
+use std::collections::HashSet;
+
+/// Hash of a k-mer, case insensitive: FNV-1a followed by a 64 bit finalizer so that
+/// minimizers are not biased towards lexicographically small k-mers
+fn kmer_hash(kmer: &[u8]) -> u64 {
+    let mut hash = kmer.iter().fold(0xcbf29ce484222325_u64, |hash, base| {
+        (hash ^ base.to_ascii_uppercase() as u64).wrapping_mul(0x100000001b3)
+    });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Minimizers of a sequence as `(hash, position)`: for every window of `w` consecutive
+/// k-mers, the k-mer with the smallest hash (leftmost one on a tie). Consecutive windows
+/// sharing a minimizer report it once. A sequence with fewer than `w` k-mers is one window.
+///
+/// K-mers are taken from the given strand only.
+pub fn minimizers(seq: &[u8], k: usize, w: usize) -> Vec<(u64, usize)> {
+    if k == 0 || w == 0 || seq.len() < k {
+        return Vec::new();
+    }
+    let hashes = seq.windows(k).map(kmer_hash).collect::<Vec<_>>();
+    let mut sketch: Vec<(u64, usize)> = Vec::new();
+    for (start, window) in hashes.windows(w.min(hashes.len())).enumerate() {
+        let minimizer = window
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, hash)| **hash)
+            .map(|(offset, hash)| (*hash, start + offset))
+            .expect("Window is never empty");
+        if sketch.last() != Some(&minimizer) {
+            sketch.push(minimizer);
+        }
+    }
+    sketch
+}
+
+/// Number of distinct minimizer hashes two sketches (see [minimizers]) have in common
+pub fn shared_minimizers(a: &[(u64, usize)], b: &[(u64, usize)]) -> usize {
+    let a = a.iter().map(|(hash, _)| *hash).collect::<HashSet<_>>();
+    let b = b.iter().map(|(hash, _)| *hash).collect::<HashSet<_>>();
+    a.intersection(&b).count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{minimizers, shared_minimizers};
+
+    const SEQ: &[u8] = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGAATTAGATCAGTTAAATGGCAGAAAACTGGCAGGGCTTTTAGTCGTG";
+
+    #[test]
+    fn minimizer_per_window() {
+        let sketch = minimizers(SEQ, 11, 5);
+
+        assert!(!sketch.is_empty());
+        assert!(sketch.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        // Every window of 5 k-mers holds a minimizer
+        assert!(sketch.windows(2).all(|pair| pair[1].1 - pair[0].1 <= 5));
+        assert!(minimizers(b"ACG", 11, 5).is_empty());
+    }
+
+    #[test]
+    fn overlapping_reads_share_minimizers() {
+        let a = minimizers(&SEQ[..70], 11, 5);
+        let b = minimizers(&SEQ[30..], 11, 5);
+        let unrelated = minimizers(
+            b"GGATCACAGTCTACACTGCTCACTCCAACCCCGGCCCCTGAGTCCGAGGAGAGGGTGCTTCAGAGTATGT",
+            11,
+            5,
+        );
+
+        assert!(shared_minimizers(&a, &b) >= 5);
+        assert_eq!(shared_minimizers(&a, &unrelated), 0);
+    }
+}
@@ -0,0 +1,310 @@
+//! Multiple sequence alignment (MSA) and statistics over it, given as one aligned row per
+//! sequence where all rows have the same length and gaps are written as `-`
+
+use std::{collections::HashMap, fmt::Display};
+
+use bio::alignment::{
+    pairwise::{Aligner, MatchFunc, Scoring},
+    poa, AlignmentOperation,
+};
+
+use crate::aliner::GapPanelty;
+
+/// Multiple sequence alignment of any number of sequences built on partial order alignment
+/// (POA): sequences are merged one by one into a graph whose heaviest path is the consensus.
+///
+/// Rows are then obtained by aligning every sequence against the consensus (center star), so
+/// the cost is `O(N * L^2)` for `N` sequences of length `L`.
+#[derive(Debug)]
+pub struct MsaBuilder<F>
+where
+    F: MatchFunc + Clone + Display,
+{
+    gap_penalty: GapPanelty,
+    score: F,
+    sequences: Vec<Vec<u8>>,
+}
+
+impl<F> MsaBuilder<F>
+where
+    F: MatchFunc + Clone + Display,
+{
+    pub fn new(gap_penalty: GapPanelty, score: F) -> Self {
+        Self {
+            gap_penalty,
+            score,
+            sequences: Vec::new(),
+        }
+    }
+
+    pub fn add_sequence(&mut self, seq: &[u8]) {
+        self.sequences.push(seq.to_vec());
+    }
+
+    fn scoring(&self) -> Scoring<F> {
+        Scoring::new(
+            self.gap_penalty.open,
+            self.gap_penalty.extend,
+            self.score.clone(),
+        )
+    }
+
+    /// Consensus of the added sequences, empty if there is none
+    pub fn consensus(&self) -> Vec<u8> {
+        tracing::info!(
+            "Building POA consensus of {} sequences using {} and {}",
+            self.sequences.len(),
+            self.gap_penalty,
+            self.score
+        );
+        let Some((first, rest)) = self.sequences.split_first() else {
+            return Vec::new();
+        };
+        let mut aligner = poa::Aligner::new(self.scoring(), first);
+        rest.iter().for_each(|seq| {
+            aligner.global(seq).add_to_graph();
+        });
+        aligner.consensus()
+    }
+
+    /// One aligned row per added sequence, in the order they were added
+    pub fn aligned_rows(&self) -> Vec<Vec<u8>> {
+        let consensus = self.consensus();
+        tracing::info!(
+            "Aligning {} sequences to the consensus",
+            self.sequences.len()
+        );
+        // For every sequence its base (or gap) at each consensus position, and the bases it
+        // has before each consensus position (or at the end) that the consensus lacks
+        let placed = self
+            .sequences
+            .iter()
+            .map(|seq| {
+                let mut aligner =
+                    Aligner::with_capacity_and_scoring(consensus.len(), seq.len(), self.scoring());
+                let alignment = aligner.global(&consensus, seq);
+                let mut bases = Vec::with_capacity(consensus.len());
+                let mut inserted = vec![Vec::new(); consensus.len() + 1];
+                let mut query_pos = 0;
+                for operation in alignment.operations {
+                    match operation {
+                        AlignmentOperation::Match | AlignmentOperation::Subst => {
+                            bases.push(seq[query_pos]);
+                            query_pos += 1;
+                        }
+                        AlignmentOperation::Ins => bases.push(b'-'),
+                        AlignmentOperation::Del => {
+                            inserted[bases.len()].push(seq[query_pos]);
+                            query_pos += 1;
+                        }
+                        _ => (),
+                    }
+                }
+                (bases, inserted)
+            })
+            .collect::<Vec<_>>();
+
+        let widths = (0..=consensus.len())
+            .map(|pos| {
+                placed
+                    .iter()
+                    .map(|(_, inserted)| inserted[pos].len())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        placed
+            .into_iter()
+            .map(|(bases, inserted)| {
+                let mut row = Vec::new();
+                for (pos, width) in widths.iter().enumerate() {
+                    row.extend_from_slice(&inserted[pos]);
+                    row.resize(row.len() + width - inserted[pos].len(), b'-');
+                    if let Some(base) = bases.get(pos) {
+                        row.push(*base);
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+}
+
+/// Length of the shortest row, i.e. columns present in every row
+fn column_count(aligned: &[Vec<u8>]) -> usize {
+    aligned.iter().map(Vec::len).min().unwrap_or(0)
+}
+
+/// Conservation of each codon of a protein coding MSA: fraction (`0.0..=1.0`) of sequences
+/// sharing the most common codon at that position.
+///
+/// Framing is not inferred: the alignment must be codon aware, i.e. the reading frame starts
+/// at the first column and gaps are only inserted as whole codons, so every 3 consecutive
+/// columns form a codon in all rows. A trailing incomplete codon is ignored. Codons are
+/// compared case insensitively, and gapped codons count as codons of their own.
+pub fn codon_conservation(aligned_cds: &[Vec<u8>]) -> Vec<f64> {
+    tracing::info!(
+        "Calculating codon conservation of {} sequences",
+        aligned_cds.len()
+    );
+    let codons = column_count(aligned_cds) / 3;
+    (0..codons)
+        .map(|codon| {
+            let mut counts = HashMap::new();
+            for row in aligned_cds {
+                let key = row[codon * 3..codon * 3 + 3].to_ascii_uppercase();
+                *counts.entry(key).or_insert(0) += 1;
+            }
+            let majority = counts.values().copied().max().unwrap_or(0);
+            majority as f64 / aligned_cds.len() as f64
+        })
+        .collect()
+}
+
+/// Fraction (`0.0..=1.0`) of sequences with a base rather than a gap, for every column
+pub fn occupancy_profile(aligned: &[Vec<u8>]) -> Vec<f64> {
+    tracing::info!(
+        "Calculating occupancy profile of {} sequences",
+        aligned.len()
+    );
+    (0..column_count(aligned))
+        .map(|column| {
+            let occupied = aligned.iter().filter(|row| row[column] != b'-').count();
+            occupied as f64 / aligned.len() as f64
+        })
+        .collect()
+}
+
+/// Number of segregating sites: columns with more than one distinct base, ignoring gaps.
+/// Bases are compared case insensitively.
+pub fn segregating_sites(aligned: &[Vec<u8>]) -> usize {
+    tracing::info!("Counting segregating sites of {} sequences", aligned.len());
+    (0..column_count(aligned))
+        .filter(|&column| {
+            let mut bases = aligned
+                .iter()
+                .map(|row| row[column].to_ascii_uppercase())
+                .filter(|base| *base != b'-');
+            bases
+                .next()
+                .is_some_and(|first| bases.any(|base| base != first))
+        })
+        .count()
+}
+
+/// Nucleotide diversity (π): average over all pairs of sequences of the fraction of differing
+/// bases, counted over the columns where neither has a gap. `0.0` for less than 2 sequences.
+pub fn nucleotide_diversity(aligned: &[Vec<u8>]) -> f64 {
+    tracing::info!(
+        "Calculating nucleotide diversity of {} sequences",
+        aligned.len()
+    );
+    let columns = column_count(aligned);
+    let mut pairs = 0;
+    let mut total = 0.0;
+    for (i, a) in aligned.iter().enumerate() {
+        for b in &aligned[i + 1..] {
+            let (differences, compared) = a[..columns]
+                .iter()
+                .zip(&b[..columns])
+                .filter(|(a, b)| **a != b'-' && **b != b'-')
+                .fold((0, 0), |(differences, compared), (a, b)| {
+                    (
+                        differences + !a.eq_ignore_ascii_case(b) as usize,
+                        compared + 1,
+                    )
+                });
+            pairs += 1;
+            if compared > 0 {
+                total += differences as f64 / compared as f64;
+            }
+        }
+    }
+    if pairs == 0 {
+        0.0
+    } else {
+        total / pairs as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::{GapPanelty, Score};
+
+    use super::{
+        codon_conservation, nucleotide_diversity, occupancy_profile, segregating_sites, MsaBuilder,
+    };
+
+    #[test]
+    fn codon_conservation_with_one_variable_codon() {
+        let aligned = vec![
+            b"ATGAAACCCT".to_vec(),
+            b"ATGAAGCCCT".to_vec(),
+            b"atgaaaccct".to_vec(),
+        ];
+
+        assert_eq!(codon_conservation(&aligned), vec![1.0, 2.0 / 3.0, 1.0]);
+    }
+
+    #[test]
+    fn segregating_sites_and_diversity() {
+        let aligned = vec![
+            b"ACGTACGT".to_vec(),
+            b"ACGTTCGA".to_vec(),
+            b"acgTA-GT".to_vec(),
+        ];
+
+        // Columns 4 and 7 are polymorphic, the gap at 5 is not
+        assert_eq!(segregating_sites(&aligned), 2);
+        // Pairs differ in 2 of 8, 0 of 7 and 2 of 7 compared bases
+        let expected = (2.0 / 8.0 + 0.0 / 7.0 + 2.0 / 7.0) / 3.0;
+        assert!((nucleotide_diversity(&aligned) - expected).abs() < 1e-12);
+        assert_eq!(nucleotide_diversity(&aligned[..1]), 0.0);
+    }
+
+    #[test]
+    fn occupancy_of_gappy_column() {
+        let aligned = vec![
+            b"AC-T".to_vec(),
+            b"AC-T".to_vec(),
+            b"A-GT".to_vec(),
+            b"AC--".to_vec(),
+        ];
+
+        assert_eq!(occupancy_profile(&aligned), vec![1.0, 0.75, 0.25, 0.75]);
+    }
+
+    #[test]
+    fn msa_consensus_of_majority() {
+        let mut msa = MsaBuilder::new(GapPanelty::new(-5, -1), Score::new(1, -1));
+        assert!(msa.consensus().is_empty());
+        // The minority sequence comes first, so it seeds the graph
+        msa.add_sequence(b"GATTACACCGTTGGCTAACG");
+        msa.add_sequence(b"GATTACACCGTAGGCTAACG");
+        msa.add_sequence(b"GATTACACCGTAGGCTAACG");
+
+        assert_eq!(msa.consensus(), b"GATTACACCGTAGGCTAACG");
+        let rows = msa.aligned_rows();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], b"GATTACACCGTTGGCTAACG");
+        assert_eq!(segregating_sites(&rows), 1);
+    }
+
+    #[test]
+    fn msa_rows_with_insertion() {
+        let mut msa = MsaBuilder::new(GapPanelty::new(-5, -1), Score::new(1, -1));
+        msa.add_sequence(b"GATTACACCGTAGGCT");
+        msa.add_sequence(b"GATTACACCGGGTAGGCT");
+        msa.add_sequence(b"GATTACACCGTAGGCT");
+
+        let rows = msa.aligned_rows();
+        assert!(rows.iter().all(|row| row.len() == 18));
+        assert_eq!(
+            occupancy_profile(&rows)
+                .iter()
+                .filter(|o| **o < 1.0)
+                .count(),
+            2
+        );
+    }
+}
@@ -1,21 +1,65 @@
-use std::fmt::Display;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    io::{self, Write},
+};
 
-use bio::alignment::pairwise::MatchFunc;
+use bio::alignment::{pairwise::MatchFunc, AlignmentOperation};
 use tabled::{Table, Tabled};
 
-use crate::aliner::DiffStat;
+use crate::{
+    aliner::{alignment_columns, DiffStat},
+    sequence::PHRED_OFFSET,
+    translation::GeneticCode,
+};
 
-#[derive(Debug, Default, Tabled)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MutationStats {
-    #[tabled(rename = "match")]
     r#match: usize,
     miss_match: usize,
     substitution: usize,
+    /// Query bases missing from the reference, as [IndelKind::Insertion]
     insertions: usize,
+    /// Reference bases missing from the query, as [IndelKind::Deletion]
     deletions: usize,
     total: usize,
 }
 
+impl Tabled for MutationStats {
+    const LENGTH: usize = 7;
+
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        let indel_ratio = self
+            .indel_ratio()
+            .map_or_else(|| "-".to_string(), |ratio| format!("{ratio:.2}"));
+        vec![
+            self.r#match.to_string().into(),
+            self.miss_match.to_string().into(),
+            self.substitution.to_string().into(),
+            self.insertions.to_string().into(),
+            self.deletions.to_string().into(),
+            self.total.to_string().into(),
+            indel_ratio.into(),
+        ]
+    }
+
+    fn headers() -> Vec<Cow<'static, str>> {
+        [
+            "match",
+            "miss_match",
+            "substitution",
+            "insertions",
+            "deletions",
+            "total",
+            "indel_ratio",
+        ]
+        .map(Cow::Borrowed)
+        .to_vec()
+    }
+}
+
 impl Display for MutationStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", Table::new(vec![self]))
@@ -23,6 +67,35 @@ impl Display for MutationStats {
 }
 
 impl MutationStats {
+    pub fn r#match(&self) -> usize {
+        self.r#match
+    }
+
+    pub fn miss_match(&self) -> usize {
+        self.miss_match
+    }
+
+    pub fn substitution(&self) -> usize {
+        self.substitution
+    }
+
+    pub fn insertions(&self) -> usize {
+        self.insertions
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.deletions
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Insertion to deletion ratio, `None` when there is no deletion
+    pub fn indel_ratio(&self) -> Option<f64> {
+        (self.deletions > 0).then(|| self.insertions as f64 / self.deletions as f64)
+    }
+
     pub fn inc_match(&mut self) {
         self.r#match += 1;
         self.total += 1
@@ -35,21 +108,221 @@ impl MutationStats {
 
     pub fn inc_substitution(&mut self) {
         self.substitution += 1;
-        self.inc_miss_match();
-        self.total += 1
+        self.inc_miss_match()
     }
 
     pub fn inc_insertions(&mut self) {
         self.insertions += 1;
-        self.inc_miss_match();
-        self.total += 1
+        self.inc_miss_match()
     }
 
     pub fn inc_deletions(&mut self) {
         self.deletions += 1;
-        self.inc_miss_match();
-        self.total += 1;
+        self.inc_miss_match()
+    }
+}
+
+/// Anything other than `A`, `C`, `G`, `T` or `U`, like the IUPAC code `N`
+fn is_ambiguous(base: u8) -> bool {
+    !matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'U')
+}
+
+/// Whether substituting `a` by `b` is a transition, i.e. between two purines (`A`, `G`) or two
+/// pyrimidines (`C`, `T`/`U`)
+pub(crate) fn is_transition(a: u8, b: u8) -> bool {
+    matches!(
+        (a.to_ascii_uppercase(), b.to_ascii_uppercase()),
+        (b'A', b'G') | (b'G', b'A') | (b'C', b'T' | b'U') | (b'T' | b'U', b'C')
+    )
+}
+
+/// Whether substituting `a` by `b` is a transversion, i.e. between a purine and a pyrimidine.
+/// Ambiguous bases like `N` make neither a transition nor a transversion.
+pub(crate) fn is_transversion(a: u8, b: u8) -> bool {
+    let purine = |base: u8| matches!(base.to_ascii_uppercase(), b'A' | b'G');
+    let pyrimidine = |base: u8| matches!(base.to_ascii_uppercase(), b'C' | b'T' | b'U');
+    (purine(a) && pyrimidine(b)) || (pyrimidine(a) && purine(b))
+}
+
+/// Kind of an indel, as seen from the query relative to the reference
+///
+/// Note that [DiffStat] aligns the reference as bio's `x`, so [AlignmentOperation::Ins] is
+/// a [IndelKind::Deletion] and [AlignmentOperation::Del] is a [IndelKind::Insertion].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndelKind {
+    /// Bases present in the query, missing from the reference
+    Insertion,
+    /// Bases present in the reference, missing from the query
+    Deletion,
+}
+
+impl Display for IndelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndelKind::Insertion => write!(f, "insertion"),
+            IndelKind::Deletion => write!(f, "deletion"),
+        }
+    }
+}
+
+fn display_bases(bases: &[u8]) -> String {
+    String::from_utf8_lossy(bases).into_owned()
+}
+
+/// A run of consecutive inserted or deleted bases
+#[derive(Debug, Clone, PartialEq, Eq, Tabled)]
+pub struct IndelEvent {
+    pub kind: IndelKind,
+    /// Reference position of the first deleted base, or the one an insertion is placed before
+    pub ref_pos: usize,
+    /// Query position of the first inserted base, or the one a deletion is placed before
+    pub query_pos: usize,
+    /// Inserted or deleted bases
+    #[tabled(display_with = "display_bases")]
+    pub bases: Vec<u8>,
+}
+
+impl IndelEvent {
+    /// Number of inserted or deleted bases
+    pub fn len(&self) -> usize {
+        self.bases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bases.is_empty()
+    }
+
+    /// Reference and query positions right after the event
+    fn end(&self) -> (usize, usize) {
+        match self.kind {
+            IndelKind::Insertion => (self.ref_pos, self.query_pos + self.len()),
+            IndelKind::Deletion => (self.ref_pos + self.len(), self.query_pos),
+        }
+    }
+}
+
+/// An indel whose length is not a multiple of three, shifting the reading frame downstream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameshiftEvent {
+    /// Reference position of the indel, see [IndelEvent::ref_pos]
+    pub ref_pos: usize,
+    /// Number of inserted or deleted bases
+    pub len: usize,
+    /// Resulting frame offset, `+1`/`+2` for insertions and `-1`/`-2` for deletions
+    pub offset: i8,
+}
+
+/// Effect of the mutations of a codon on the protein, see [Muatation::classify_coding]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodingEffect {
+    /// Index of the codon in the reading frame of the reference
+    pub codon_index: usize,
+    /// Amino acid of the reference codon
+    pub ref_aa: u8,
+    /// Amino acid of the mutated codon, `X` for a frameshift
+    pub alt_aa: u8,
+    pub synonymous: bool,
+    /// An indel in this codon shifts the reading frame, see [FrameshiftEvent]
+    pub frameshift: bool,
+}
+
+/// Kind of a single base mutation, as seen from the query relative to the reference (see
+/// [IndelKind] for how it maps to bio's operations)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MutationKind {
+    Substitution,
+    /// Base present in the query, missing from the reference
+    Insertion,
+    /// Base present in the reference, missing from the query
+    Deletion,
+}
+
+impl Display for MutationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MutationKind::Substitution => write!(f, "substitution"),
+            MutationKind::Insertion => write!(f, "insertion"),
+            MutationKind::Deletion => write!(f, "deletion"),
+        }
+    }
+}
+
+/// Base of a column, `-` for the gapped side
+fn display_base(base: &Option<u8>) -> String {
+    base.map_or_else(|| "-".to_string(), |base| (base as char).to_string())
+}
+
+/// A single mutated alignment column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Tabled)]
+pub struct MutationEvent {
+    /// Reference position of the column, for an insertion the one it is placed before
+    pub ref_pos: usize,
+    /// Query position of the column, for a deletion the one it is placed before
+    pub query_pos: usize,
+    pub kind: MutationKind,
+    /// Reference base, `None` for an insertion
+    #[tabled(display_with = "display_base")]
+    pub ref_base: Option<u8>,
+    /// Query base, `None` for a deletion
+    #[tabled(display_with = "display_base")]
+    pub query_base: Option<u8>,
+}
+
+/// [MutationStats] of one query, tagged with the query and the reference it was aligned to
+#[derive(Debug, Tabled)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MutationReport {
+    query: String,
+    reference: String,
+    #[tabled(inline)]
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    stats: MutationStats,
+}
+
+impl MutationReport {
+    pub fn new<Q, R>(query: Q, reference: R, stats: MutationStats) -> Self
+    where
+        Q: Into<String>,
+        R: Into<String>,
+    {
+        Self {
+            query: query.into(),
+            reference: reference.into(),
+            stats,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    pub fn stats(&self) -> &MutationStats {
+        &self.stats
+    }
+}
+
+/// Shift an indel of `bases` at `(ref_pos, query_pos)` left while the reference base before
+/// it equals its last base, rotating `bases` accordingly, but not past `floor`. Returns the
+/// new position.
+fn shift_left(
+    reference: &[u8],
+    (mut ref_pos, mut query_pos): (usize, usize),
+    bases: &mut [u8],
+    (ref_floor, query_floor): (usize, usize),
+) -> (usize, usize) {
+    while ref_pos > ref_floor
+        && query_pos > query_floor
+        && bases.last() == Some(&reference[ref_pos - 1])
+    {
+        bases.rotate_right(1);
+        ref_pos -= 1;
+        query_pos -= 1;
     }
+    (ref_pos, query_pos)
 }
 
 #[derive(Debug)]
@@ -88,21 +361,606 @@ where
                     match operation {
                         bio::alignment::AlignmentOperation::Match => ms.inc_match(),
                         bio::alignment::AlignmentOperation::Subst => ms.inc_substitution(),
-                        bio::alignment::AlignmentOperation::Del => ms.inc_deletions(),
-                        bio::alignment::AlignmentOperation::Ins => ms.inc_insertions(),
+                        // The reference is bio's `x`, see [IndelKind]
+                        bio::alignment::AlignmentOperation::Del => ms.inc_insertions(),
+                        bio::alignment::AlignmentOperation::Ins => ms.inc_deletions(),
                         _ => (),
                     }
                     ms
                 })
         })
     }
+
+    /// [MutationStats] like [Muatation::mutation_score], leaving out the alignment columns at
+    /// `excluded` reference positions (e.g. primer regions). Insertions are left out when the
+    /// reference position they are placed before is excluded.
+    pub fn stats_excluding(&self, excluded: &HashSet<usize>) -> MutationStats {
+        tracing::info!(
+            "Calculating mutation score excluding {} positions",
+            excluded.len()
+        );
+        let Some(alignment) = self.diffstat.alignment() else {
+            return MutationStats::default();
+        };
+        alignment_columns(alignment)
+            .filter(|(_, ref_pos, _)| !excluded.contains(ref_pos))
+            .fold(MutationStats::default(), |mut ms, (operation, _, _)| {
+                match operation {
+                    AlignmentOperation::Match => ms.inc_match(),
+                    AlignmentOperation::Subst => ms.inc_substitution(),
+                    AlignmentOperation::Del => ms.inc_insertions(),
+                    AlignmentOperation::Ins => ms.inc_deletions(),
+                    _ => (),
+                }
+                ms
+            })
+    }
+
+    /// Bases of the reference and query present in a column, `None` for the gapped side
+    fn column_bases(
+        &self,
+        operation: AlignmentOperation,
+        ref_pos: usize,
+        query_pos: usize,
+    ) -> (Option<u8>, Option<u8>) {
+        let reference = self.diffstat.reference();
+        let query = self.diffstat.query();
+        match operation {
+            AlignmentOperation::Match | AlignmentOperation::Subst => {
+                (Some(reference[ref_pos]), Some(query[query_pos]))
+            }
+            AlignmentOperation::Ins => (Some(reference[ref_pos]), None),
+            AlignmentOperation::Del => (None, Some(query[query_pos])),
+            _ => (None, None),
+        }
+    }
+
+    /// Whether any base present in the column is ambiguous
+    fn is_ambiguous_column(
+        &self,
+        operation: AlignmentOperation,
+        ref_pos: usize,
+        query_pos: usize,
+    ) -> bool {
+        let (ref_base, query_base) = self.column_bases(operation, ref_pos, query_pos);
+        ref_base.into_iter().chain(query_base).any(is_ambiguous)
+    }
+
+    /// Number of matches where the bases are ambiguous, e.g. an `N` aligned to an `N`
+    pub fn ambiguous_matches(&self) -> Option<usize> {
+        self.diffstat.alignment().map(|alignment| {
+            alignment_columns(alignment)
+                .filter(|(operation, ref_pos, query_pos)| {
+                    *operation == AlignmentOperation::Match
+                        && self.is_ambiguous_column(*operation, *ref_pos, *query_pos)
+                })
+                .count()
+        })
+    }
+
+    /// Identity (`0.0..=1.0`) where columns involving an ambiguous base are left out of both
+    /// the matches and the aligned length.
+    ///
+    /// Returns `None` if there is no unambiguous column.
+    pub fn strict_identity(&self) -> Option<f64> {
+        tracing::info!("Calcualting strict identity");
+        let alignment = self.diffstat.alignment()?;
+        let (matches, aligned) = alignment_columns(alignment)
+            .filter(|(operation, ref_pos, query_pos)| {
+                !self.is_ambiguous_column(*operation, *ref_pos, *query_pos)
+            })
+            .fold(
+                (0, 0),
+                |(matches, aligned), (operation, _, _)| match operation {
+                    AlignmentOperation::Match => (matches + 1, aligned + 1),
+                    AlignmentOperation::Subst
+                    | AlignmentOperation::Ins
+                    | AlignmentOperation::Del => (matches, aligned + 1),
+                    _ => (matches, aligned),
+                },
+            );
+        (aligned > 0).then(|| matches as f64 / aligned as f64)
+    }
+
+    /// Number of substitutions that are transitions (`A`↔`G` or `C`↔`T`)
+    pub fn transition_count(&self) -> usize {
+        self.substitutions()
+            .filter(|(ref_base, query_base)| is_transition(*ref_base, *query_base))
+            .count()
+    }
+
+    /// Number of substitutions that are transversions (purine ↔ pyrimidine), substitutions
+    /// involving an ambiguous base like `N` are not counted
+    pub fn transversion_count(&self) -> usize {
+        self.substitutions()
+            .filter(|(ref_base, query_base)| is_transversion(*ref_base, *query_base))
+            .count()
+    }
+
+    /// Transition to transversion (Ti/Tv) ratio of the substitutions, `None` when there is no
+    /// transversion
+    pub fn ti_tv_ratio(&self) -> Option<f64> {
+        tracing::info!("Calculating Ti/Tv ratio");
+        let transversions = self.transversion_count();
+        (transversions > 0).then(|| self.transition_count() as f64 / transversions as f64)
+    }
+
+    /// `(reference base, query base)` of every substitution
+    fn substitutions(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.mutation_events()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .filter_map(|event| Some((event.ref_base?, event.query_base?)))
+    }
+
+    /// Apply the detected substitutions, insertions and deletions to the reference, producing a
+    /// new (mutated) reference.
+    ///
+    /// Reference bases outside the aligned region are kept as they are, so for a global
+    /// alignment this reconstructs the query.
+    pub fn apply_to_reference(&self) -> Vec<u8> {
+        tracing::info!("Applying mutations to reference");
+        let reference = self.diffstat.reference();
+        let Some(alignment) = self.diffstat.alignment() else {
+            return reference.to_vec();
+        };
+        let mut mutated = reference[..alignment.xstart].to_vec();
+        mutated.extend(alignment_columns(alignment).filter_map(
+            |(operation, ref_pos, query_pos)| self.column_bases(operation, ref_pos, query_pos).1,
+        ));
+        mutated.extend_from_slice(&reference[alignment.xend..]);
+        mutated
+    }
+
+    /// Substitution and indel rates per aligned base, as `(substitution_rate, indel_rate)`.
+    ///
+    /// Indels are counted as events (see [Muatation::structural_only]) rather than bases, so a
+    /// 5 base deletion adds as much as a 1 base one. Both rates are `0.0` when
+    /// `aligned_length` is `0`.
+    pub fn rates(&self, aligned_length: usize) -> (f64, f64) {
+        tracing::info!("Calculating substitution and indel rates");
+        if aligned_length == 0 {
+            return (0.0, 0.0);
+        }
+        let substitutions = self
+            .mutation_events()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .count();
+        let indels = self.structural_only().len();
+        (
+            substitutions as f64 / aligned_length as f64,
+            indels as f64 / aligned_length as f64,
+        )
+    }
+
+    /// Number of substitutions whose query base has a Phred quality of at least `min_q`.
+    ///
+    /// `qual` holds the Phred+33 encoded qualities of the aligned query, as in FASTQ. Bases
+    /// without a quality are not counted.
+    pub fn mismatch_count_at_quality(&self, qual: &[u8], min_q: u8) -> usize {
+        self.mutation_events()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .filter(|event| {
+                qual.get(event.query_pos)
+                    .is_some_and(|q| q.saturating_sub(PHRED_OFFSET) >= min_q)
+            })
+            .count()
+    }
+
+    /// Lazily located mutations, see [Muatation::mutation_positions]
+    fn mutation_events(&self) -> impl Iterator<Item = MutationEvent> + '_ {
+        self.diffstat
+            .alignment()
+            .into_iter()
+            .flat_map(alignment_columns)
+            .filter_map(|(operation, ref_pos, query_pos)| {
+                let kind = match operation {
+                    AlignmentOperation::Subst => MutationKind::Substitution,
+                    AlignmentOperation::Del => MutationKind::Insertion,
+                    AlignmentOperation::Ins => MutationKind::Deletion,
+                    _ => return None,
+                };
+                let (ref_base, query_base) = self.column_bases(operation, ref_pos, query_pos);
+                Some(MutationEvent {
+                    ref_pos,
+                    query_pos,
+                    kind,
+                    ref_base,
+                    query_base,
+                })
+            })
+    }
+
+    /// Every substitution, insertion and deletion along with its position, one event per
+    /// alignment column
+    pub fn mutation_positions(&self) -> Vec<MutationEvent> {
+        tracing::info!("Locating mutations");
+        self.mutation_events().collect()
+    }
+
+    /// The first `n` events of [Muatation::mutation_positions], stopping the search as soon
+    /// as they are found
+    pub fn first_n_mutations(&self, n: usize) -> Vec<MutationEvent> {
+        tracing::info!("Locating first {} mutations", n);
+        self.mutation_events().take(n).collect()
+    }
+
+    /// Mutation counts binned along the reference, as `(window_start, mutation_count)` for
+    /// every `window` bases wide bin from the start of the reference, empty bins included.
+    ///
+    /// Every mutated column counts once, an insertion past the end of the reference falls
+    /// into the last bin. Empty for a `window` of 0.
+    pub fn mutation_density(&self, window: usize) -> Vec<(usize, usize)> {
+        if window == 0 {
+            return Vec::new();
+        }
+        tracing::info!("Calculating mutation density over {} bp windows", window);
+        let reference_len = self.diffstat.reference().len();
+        let mut bins = vec![0; reference_len.div_ceil(window).max(1)];
+        let last = bins.len() - 1;
+        self.mutation_events()
+            .for_each(|event| bins[(event.ref_pos / window).min(last)] += 1);
+        bins.into_iter()
+            .enumerate()
+            .map(|(bin, count)| (bin * window, count))
+            .collect()
+    }
+
+    /// Structural differences only: insertions and deletions collapsed into runs, while
+    /// substitutions are ignored
+    pub fn structural_only(&self) -> Vec<IndelEvent> {
+        tracing::info!("Collapsing indels into events");
+        let Some(alignment) = self.diffstat.alignment() else {
+            return Vec::new();
+        };
+        alignment_columns(alignment).fold(
+            Vec::new(),
+            |mut events: Vec<IndelEvent>, (operation, ref_pos, query_pos)| {
+                let (kind, base) = match self.column_bases(operation, ref_pos, query_pos) {
+                    (Some(base), None) => (IndelKind::Deletion, base),
+                    (None, Some(base)) => (IndelKind::Insertion, base),
+                    _ => return events,
+                };
+                match events.last_mut() {
+                    Some(last) if last.kind == kind && last.end() == (ref_pos, query_pos) => {
+                        last.bases.push(base)
+                    }
+                    _ => events.push(IndelEvent {
+                        kind,
+                        ref_pos,
+                        query_pos,
+                        bases: vec![base],
+                    }),
+                }
+                events
+            },
+        )
+    }
+
+    /// Every mutation like [Muatation::mutation_positions], with each indel shifted as far
+    /// left as possible without changing the resulting sequence, following the VCF
+    /// normalization convention. Indels never move past the previous mutation.
+    ///
+    /// Events are sorted by reference and then query position.
+    pub fn left_align_indels(&self) -> Vec<MutationEvent> {
+        tracing::info!("Left aligning indels");
+        let reference = self.diffstat.reference();
+        let positions = self.mutation_events().collect::<Vec<_>>();
+        let mut events = positions
+            .iter()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for indel in self.structural_only() {
+            let start = positions
+                .iter()
+                .position(|event| {
+                    (event.ref_pos, event.query_pos) == (indel.ref_pos, indel.query_pos)
+                })
+                .expect("Indel events are made of mutated columns");
+            // Positions right after the previous mutation
+            let (ref_floor, query_floor) = start.checked_sub(1).map_or((0, 0), |previous| {
+                let previous = &positions[previous];
+                (
+                    previous.ref_pos + (previous.kind != MutationKind::Insertion) as usize,
+                    previous.query_pos + (previous.kind != MutationKind::Deletion) as usize,
+                )
+            });
+
+            let mut bases = indel.bases;
+            let (ref_pos, query_pos) = shift_left(
+                reference,
+                (indel.ref_pos, indel.query_pos),
+                &mut bases,
+                (ref_floor, query_floor),
+            );
+
+            events.extend(
+                bases
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, &base)| match indel.kind {
+                        IndelKind::Deletion => MutationEvent {
+                            ref_pos: ref_pos + offset,
+                            query_pos,
+                            kind: MutationKind::Deletion,
+                            ref_base: Some(base),
+                            query_base: None,
+                        },
+                        IndelKind::Insertion => MutationEvent {
+                            ref_pos,
+                            query_pos: query_pos + offset,
+                            kind: MutationKind::Insertion,
+                            ref_base: None,
+                            query_base: Some(base),
+                        },
+                    }),
+            );
+        }
+        events.sort_by_key(|event| (event.ref_pos, event.query_pos));
+        events
+    }
+
+    /// Indel events shifting the reading frame, i.e. with a length that is not a multiple of
+    /// three
+    pub fn frameshifts(&self) -> Vec<FrameshiftEvent> {
+        tracing::info!("Detecting frameshifts");
+        self.structural_only()
+            .into_iter()
+            .filter(|event| event.len() % 3 != 0)
+            .map(|event| {
+                let shift = (event.len() % 3) as i8;
+                FrameshiftEvent {
+                    ref_pos: event.ref_pos,
+                    len: event.len(),
+                    offset: match event.kind {
+                        IndelKind::Insertion => shift,
+                        IndelKind::Deletion => -shift,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Effect on the protein of every codon holding substitutions or a frameshift, with the
+    /// reference coding from offset `frame` (`0..=2`) on, sorted by codon.
+    ///
+    /// All substitutions of a codon are applied together and translated with the standard
+    /// genetic code. Incomplete codons at either end of the reference are skipped.
+    pub fn classify_coding(&self, frame: usize) -> Vec<CodingEffect> {
+        tracing::info!("Classifying coding effects in frame {}", frame);
+        let reference = self.diffstat.reference();
+        let codon = |codon_index: usize| {
+            let start = frame + codon_index * 3;
+            reference.get(start..start + 3)
+        };
+        let code = GeneticCode::Standard;
+
+        let mut mutated: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for event in self.mutation_events() {
+            let (MutationKind::Substitution, Some(query_base)) = (event.kind, event.query_base)
+            else {
+                continue;
+            };
+            let Some(offset) = event.ref_pos.checked_sub(frame) else {
+                continue;
+            };
+            if let Some(ref_codon) = codon(offset / 3) {
+                mutated
+                    .entry(offset / 3)
+                    .or_insert_with(|| ref_codon.to_vec())[offset % 3] = query_base;
+            }
+        }
+        let substitutions = mutated.into_iter().filter_map(|(codon_index, alt_codon)| {
+            let ref_aa = code.translate_codon(codon(codon_index)?);
+            let alt_aa = code.translate_codon(&alt_codon);
+            Some(CodingEffect {
+                codon_index,
+                ref_aa,
+                alt_aa,
+                synonymous: ref_aa == alt_aa,
+                frameshift: false,
+            })
+        });
+        let frameshifts = self.frameshifts().into_iter().filter_map(|event| {
+            let codon_index = event.ref_pos.checked_sub(frame)? / 3;
+            Some(CodingEffect {
+                codon_index,
+                ref_aa: code.translate_codon(codon(codon_index)?),
+                alt_aa: b'X',
+                synonymous: false,
+                frameshift: true,
+            })
+        });
+        let mut effects = substitutions.chain(frameshifts).collect::<Vec<_>>();
+        effects.sort_by_key(|effect| (effect.codon_index, effect.frameshift));
+        effects
+    }
+
+    /// `(POS, REF, ALT)` of every VCF record, sorted by position. Indels are anchored on the
+    /// preceding reference base, or on the following one when they start the reference.
+    fn vcf_records(&self) -> Vec<(usize, Vec<u8>, Vec<u8>)> {
+        let reference = self.diffstat.reference();
+        let snps = self
+            .mutation_positions()
+            .into_iter()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .filter_map(|event| {
+                Some((
+                    event.ref_pos + 1,
+                    vec![event.ref_base?],
+                    vec![event.query_base?],
+                ))
+            });
+        let indels = self.structural_only().into_iter().filter_map(|event| {
+            let (deleted, inserted) = match event.kind {
+                IndelKind::Deletion => (event.bases, Vec::new()),
+                IndelKind::Insertion => (Vec::new(), event.bases),
+            };
+            if event.ref_pos > 0 {
+                let anchor = reference[event.ref_pos - 1];
+                Some((
+                    event.ref_pos,
+                    [&[anchor], deleted.as_slice()].concat(),
+                    [&[anchor], inserted.as_slice()].concat(),
+                ))
+            } else {
+                let anchor = *reference.get(deleted.len())?;
+                Some((
+                    1,
+                    [deleted.as_slice(), &[anchor]].concat(),
+                    [inserted.as_slice(), &[anchor]].concat(),
+                ))
+            }
+        });
+        let mut records = snps.chain(indels).collect::<Vec<_>>();
+        records.sort_by_key(|(pos, _, _)| *pos);
+        records
+    }
+
+    /// Write the substitutions and indels as a minimal VCF (v4.2), one record per SNP or
+    /// indel event with 1-based positions on `chrom`
+    pub fn to_vcf<W: Write>(&self, mut writer: W, chrom: &str) -> io::Result<()> {
+        tracing::info!("Writing VCF");
+        writeln!(writer, "##fileformat=VCFv4.2")?;
+        writeln!(writer, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+        for (pos, reference, alternate) in self.vcf_records() {
+            writeln!(
+                writer,
+                "{chrom}\t{pos}\t.\t{}\t{}\t.\tPASS\t.",
+                display_bases(&reference),
+                display_bases(&alternate)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Compact edit script of the query against the reference in an HGVS like syntax with
+    /// 1-based reference positions, e.g. `12A>G, 45_46insT, 78delC`. Substitutions are written
+    /// per base as `12A>G`, deletions as `78delC` or `78_80delCTG` and insertions between their
+    /// flanking reference bases as `45_46insT`. An insertion before the first or after the last
+    /// reference base has a single flanking base, and is anchored on it alone, as `1insT` or
+    /// `80insT` for an 80 bases reference.
+    pub fn edit_script(&self) -> Option<String> {
+        tracing::info!("Writing edit script");
+        self.diffstat.alignment()?;
+        let reference_len = self.diffstat.reference().len();
+        let substitutions = self
+            .mutation_events()
+            .filter(|event| event.kind == MutationKind::Substitution)
+            .map(|event| {
+                let edit = format!(
+                    "{}{}>{}",
+                    event.ref_pos + 1,
+                    display_base(&event.ref_base),
+                    display_base(&event.query_base)
+                );
+                (event.ref_pos, event.query_pos, edit)
+            });
+        let indels = self.structural_only().into_iter().map(|indel| {
+            let bases = display_bases(&indel.bases);
+            let edit = match indel.kind {
+                IndelKind::Insertion if indel.ref_pos == 0 => format!("1ins{bases}"),
+                IndelKind::Insertion if indel.ref_pos == reference_len => {
+                    format!("{reference_len}ins{bases}")
+                }
+                IndelKind::Insertion => {
+                    format!("{}_{}ins{bases}", indel.ref_pos, indel.ref_pos + 1)
+                }
+                IndelKind::Deletion if indel.len() == 1 => {
+                    format!("{}del{bases}", indel.ref_pos + 1)
+                }
+                IndelKind::Deletion => {
+                    format!(
+                        "{}_{}del{bases}",
+                        indel.ref_pos + 1,
+                        indel.ref_pos + indel.len()
+                    )
+                }
+            };
+            (indel.ref_pos, indel.query_pos, edit)
+        });
+        let mut edits = substitutions.chain(indels).collect::<Vec<_>>();
+        edits.sort_by_key(|(ref_pos, query_pos, _)| (*ref_pos, *query_pos));
+        Some(
+            edits
+                .into_iter()
+                .map(|(_, _, edit)| edit)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+/// Identity of a variant across queries: its query position is left out, as that shifts with
+/// indels elsewhere in each query. Inserted bases placed before the same reference position
+/// are told apart by their offset in the insertion.
+type VariantKey = (usize, usize, MutationKind, Option<u8>, Option<u8>);
+
+/// Merge variants of many queries aligned to the same reference, counting how many queries
+/// carry each distinct variant
+#[derive(Debug, Default)]
+pub struct VariantAggregator {
+    variants: HashMap<VariantKey, (MutationEvent, usize)>,
+}
+
+impl VariantAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add variants of one more query
+    pub fn add<F>(&mut self, mutation: &Muatation<F>)
+    where
+        F: MatchFunc + Clone + Display,
+    {
+        let mut insertion_offset = 0;
+        let mut previous: Option<MutationEvent> = None;
+        for event in mutation.mutation_events() {
+            insertion_offset = match previous {
+                Some(prev)
+                    if event.kind == MutationKind::Insertion
+                        && prev.kind == MutationKind::Insertion
+                        && prev.ref_pos == event.ref_pos =>
+                {
+                    insertion_offset + 1
+                }
+                _ => 0,
+            };
+            previous = Some(event);
+            let key = (
+                event.ref_pos,
+                insertion_offset,
+                event.kind,
+                event.ref_base,
+                event.query_base,
+            );
+            self.variants.entry(key).or_insert((event, 0)).1 += 1;
+        }
+    }
+
+    /// Distinct variants sorted by reference position, each with the number of queries
+    /// carrying it. The event is the one of the first query the variant was seen in.
+    pub fn finish(self) -> Vec<(MutationEvent, usize)> {
+        let mut variants = self.variants.into_iter().collect::<Vec<_>>();
+        variants.sort_by_key(|(key, _)| *key);
+        variants.into_iter().map(|(_, variant)| variant).collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::aliner::{DiffStat, Score};
+    use std::collections::HashSet;
+
+    use crate::{
+        aliner::{DiffStat, GapPanelty, Score},
+        bench_support::random_dna,
+    };
 
-    use super::Muatation;
+    use super::{
+        shift_left, CodingEffect, FrameshiftEvent, IndelEvent, IndelKind, Muatation, MutationEvent,
+        MutationKind, MutationStats, VariantAggregator,
+    };
 
     #[test]
     #[should_panic]
@@ -142,4 +1000,486 @@ AAAAACCGTTGACGGCCA--A
 "#;
         assert_eq!(pretty, expected);
     }
+
+    #[test]
+    fn strict_identity_skips_ambiguous_matches() {
+        let mut diffstat = DiffStat::new(
+            "ACGTNACGTA",
+            "ACGTNACCTA",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+
+        assert_eq!(mutation.ambiguous_matches(), Some(1));
+        assert_eq!(mutation.strict_identity(), Some(8.0 / 9.0));
+    }
+
+    #[test]
+    fn apply_to_reference_reconstructs_query() {
+        let query = "AAAAACCGTTGACGGCCAA";
+        let mut diffstat = DiffStat::new(
+            "CCGTCCGGCAAGGG",
+            query,
+            (-1, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+
+        assert_eq!(mutation.apply_to_reference(), query.as_bytes());
+    }
+
+    #[test]
+    fn structural_only_ignores_substitutions() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+
+        assert_eq!(
+            mutation.structural_only(),
+            vec![
+                IndelEvent {
+                    kind: IndelKind::Deletion,
+                    ref_pos: 15,
+                    query_pos: 15,
+                    bases: b"TA".to_vec(),
+                },
+                IndelEvent {
+                    kind: IndelKind::Insertion,
+                    ref_pos: 27,
+                    query_pos: 25,
+                    bases: b"T".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_aggregator_counts_queries() {
+        let reference = "GATTACACCGTAGGCTAACG";
+        let queries = [
+            "GATTACACCGTTGGCTAACG",
+            "GATTACACCGTTGGCTAACG",
+            "GATTACACCGTAGGCTAACG",
+        ];
+        let mut aggregator = VariantAggregator::new();
+        for query in queries {
+            let mut diffstat =
+                DiffStat::new(reference, query, (-5, -1), Into::<Score>::into((1, -1)));
+            diffstat.pairwise_aligner_global();
+            aggregator.add(&Muatation::from(&diffstat));
+        }
+
+        assert_eq!(
+            aggregator.finish(),
+            vec![(
+                MutationEvent {
+                    ref_pos: 11,
+                    query_pos: 11,
+                    kind: MutationKind::Substitution,
+                    ref_base: Some(b'A'),
+                    query_base: Some(b'T'),
+                },
+                2
+            )]
+        );
+    }
+
+    #[test]
+    fn rates_count_indel_events() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTGGCCATGGACT",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let aligned_length = diffstat.alignment().map_or(0, |a| a.operations.len());
+        let mutation = Muatation::from(&diffstat);
+
+        assert_eq!(aligned_length, 33);
+        assert_eq!(mutation.rates(aligned_length), (2.0 / 33.0, 1.0 / 33.0));
+        assert_eq!(mutation.rates(0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn frameshifts_skip_codon_indels() {
+        let reference = "ATGGCTAAACGTTTAGCCGGTACCGATTGA";
+        let frameshift = |query: &str| {
+            let mut diffstat =
+                DiffStat::new(reference, query, (-5, -1), Into::<Score>::into((1, -1)));
+            diffstat.pairwise_aligner_global();
+            Muatation::from(&diffstat).frameshifts()
+        };
+
+        // 1 base insertion of a C
+        assert_eq!(
+            frameshift("ATGGCTAAACGTCTTAGCCGGTACCGATTGA"),
+            vec![FrameshiftEvent {
+                ref_pos: 12,
+                len: 1,
+                offset: 1,
+            }]
+        );
+        // 3 base insertion of GAT, an extra codon keeps the frame
+        assert_eq!(frameshift("ATGGCTAAACGTGATTTAGCCGGTACCGATTGA"), vec![]);
+    }
+
+    #[test]
+    fn left_align_indels_in_homopolymer() {
+        let left_aligned = |reference: &str, query: &str| {
+            let mut diffstat =
+                DiffStat::new(reference, query, (-5, -1), Into::<Score>::into((1, -1)));
+            diffstat.pairwise_aligner_global();
+            Muatation::from(&diffstat).left_align_indels()
+        };
+
+        // One of the four Ts is deleted
+        assert_eq!(
+            left_aligned("GCGATTTTACA", "GCGATTTACA"),
+            vec![MutationEvent {
+                ref_pos: 4,
+                query_pos: 4,
+                kind: MutationKind::Deletion,
+                ref_base: Some(b'T'),
+                query_base: None,
+            }]
+        );
+        // An extra A in the run of As
+        assert_eq!(
+            left_aligned("CCGAAAATGCA", "CCGAAAAATGCA"),
+            vec![MutationEvent {
+                ref_pos: 3,
+                query_pos: 3,
+                kind: MutationKind::Insertion,
+                ref_base: None,
+                query_base: Some(b'A'),
+            }]
+        );
+    }
+
+    #[test]
+    fn shift_left_to_start_of_repeat() {
+        // Deletion of the last CA of CACACA
+        let mut bases = b"CA".to_vec();
+        assert_eq!(
+            shift_left(b"GTCACACAGG", (6, 6), &mut bases, (0, 0)),
+            (2, 2)
+        );
+        assert_eq!(bases, b"CA");
+
+        // Same deletion written as AC, with a previous mutation ending at 4
+        let mut bases = b"AC".to_vec();
+        assert_eq!(
+            shift_left(b"GTCACACAGG", (5, 5), &mut bases, (4, 4)),
+            (4, 4)
+        );
+        assert_eq!(bases, b"CA");
+    }
+
+    #[test]
+    fn mismatch_count_by_quality() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GATTCCACCGTTGGCTAACC",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+        // Substitutions at 4 (Q10), 11 (Q20) and 19 (Q40)
+        let mut qual = vec![b'I'; 20];
+        qual[4] = b'+';
+        qual[11] = b'5';
+
+        assert_eq!(mutation.mismatch_count_at_quality(&qual, 0), 3);
+        assert_eq!(mutation.mismatch_count_at_quality(&qual, 20), 2);
+        assert_eq!(mutation.mismatch_count_at_quality(&qual, 30), 1);
+        assert_eq!(mutation.mismatch_count_at_quality(&qual, 41), 0);
+    }
+
+    #[test]
+    fn total_counts_each_column_once() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let stats = Muatation::from(&diffstat).mutation_score().unwrap();
+
+        assert_eq!(stats.miss_match(), 4);
+        assert_eq!(
+            stats.total(),
+            stats.r#match() + stats.substitution() + stats.insertions() + stats.deletions()
+        );
+        assert_eq!(stats.total(), 34);
+    }
+
+    #[test]
+    fn indel_ratio_of_counts() {
+        let mut stats = MutationStats::default();
+        stats.inc_insertions();
+        stats.inc_insertions();
+        assert_eq!(stats.indel_ratio(), None);
+
+        stats.inc_deletions();
+        assert_eq!(stats.indel_ratio(), Some(2.0));
+        stats.inc_deletions();
+        stats.inc_deletions();
+        stats.inc_deletions();
+        assert_eq!(stats.indel_ratio(), Some(0.5));
+    }
+
+    #[test]
+    fn indel_ratio_agrees_with_structural_only() {
+        // Query lacks the `TAG` at 10..13 of the reference
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTACACCGGCTAACGTTAGCCATGGACT",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+        let stats = mutation.mutation_score().unwrap();
+
+        let events = mutation.structural_only();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, IndelKind::Deletion);
+        assert_eq!(stats.deletions(), events[0].bases.len());
+        assert_eq!(stats.insertions(), 0);
+        assert_eq!(stats.indel_ratio(), Some(0.0));
+    }
+
+    #[test]
+    fn stats_excluding_snp_position() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            "GATTACACCGTTGGCTAACG",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+
+        let all = mutation.stats_excluding(&HashSet::new());
+        assert_eq!(all.substitution(), 1);
+        assert_eq!(all.r#match(), 19);
+
+        let excluded = mutation.stats_excluding(&HashSet::from([11]));
+        assert_eq!(excluded.substitution(), 0);
+        assert_eq!(excluded.r#match(), 19);
+    }
+
+    #[test]
+    fn first_n_of_five_mutations() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GTTTACACCGTTGGCTAACCTTAGCCATGCACA",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+        let all = mutation.mutation_positions();
+
+        assert_eq!(all.len(), 5);
+        assert_eq!(mutation.first_n_mutations(2), all[..2]);
+        assert_eq!(mutation.first_n_mutations(10), all);
+    }
+
+    #[test]
+    fn substitution_at_reference_coordinate() {
+        let mut diffstat = DiffStat::new(
+            "TTTTTGATTACACCGTAGGC",
+            "GATTACACGGTAGGC",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        // Local alignment starting at reference position 5
+        diffstat.pairwise_aligner_local();
+
+        assert_eq!(
+            Muatation::from(&diffstat).mutation_positions(),
+            vec![MutationEvent {
+                ref_pos: 13,
+                query_pos: 8,
+                kind: MutationKind::Substitution,
+                ref_base: Some(b'C'),
+                query_base: Some(b'G'),
+            }]
+        );
+    }
+
+    #[test]
+    fn vcf_of_known_alignment() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTCCACCGTAGGCACGTTAGCCATTGGACT",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mut vcf = Vec::new();
+        Muatation::from(&diffstat).to_vcf(&mut vcf, "ref1").unwrap();
+
+        assert_eq!(
+            String::from_utf8(vcf).unwrap(),
+            "##fileformat=VCFv4.2\n\
+             #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+             ref1\t5\t.\tA\tC\t.\tPASS\t.\n\
+             ref1\t15\t.\tCTA\tC\t.\tPASS\t.\n\
+             ref1\t27\t.\tA\tAT\t.\tPASS\t.\n"
+        );
+    }
+
+    #[test]
+    fn ti_tv_ratio_of_substitutions() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACG",
+            // A>G and C>T transitions, G>T transversion
+            "GATTGCACCTTAGGCTAATG",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+
+        assert_eq!(mutation.transition_count(), 2);
+        assert_eq!(mutation.transversion_count(), 1);
+        assert_eq!(mutation.ti_tv_ratio(), Some(2.0));
+
+        let mut diffstat = DiffStat::new("GATTACA", "GATTGCA", (-5, -1), Score::new(1, -1));
+        diffstat.pairwise_aligner_global();
+        assert_eq!(Muatation::from(&diffstat).ti_tv_ratio(), None);
+
+        // A>N is neither a transition nor a transversion
+        let mut diffstat = DiffStat::new("GATTACA", "GATTNCA", (-5, -1), Score::new(1, -1));
+        diffstat.pairwise_aligner_global();
+        let mutation = Muatation::from(&diffstat);
+        assert_eq!(mutation.transition_count(), 0);
+        assert_eq!(mutation.transversion_count(), 0);
+    }
+
+    #[test]
+    fn mutation_density_of_clustered_mutations() {
+        let reference = random_dna(300, 7);
+        let mut query = reference.clone();
+        for pos in [10, 40, 80] {
+            query[pos] = if query[pos] == b'A' { b'C' } else { b'A' };
+        }
+        let mut diffstat = DiffStat::new(
+            &reference[..],
+            &query[..],
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+
+        let mutation = Muatation::from(&diffstat);
+        assert_eq!(
+            mutation.mutation_density(100),
+            vec![(0, 3), (100, 0), (200, 0)]
+        );
+        assert!(mutation.mutation_density(0).is_empty());
+    }
+
+    #[test]
+    fn synonymous_and_non_synonymous_codons() {
+        let reference = "ATGGCTAAACGTTTAGCCGGTACCGATTGA";
+        let classify = |query: &str| {
+            let mut diffstat =
+                DiffStat::new(reference, query, (-5, -1), Into::<Score>::into((1, -1)));
+            diffstat.pairwise_aligner_global();
+            Muatation::from(&diffstat).classify_coding(0)
+        };
+
+        // GCT to GCC keeps the alanine, AAA to GAA turns lysine into glutamic acid
+        assert_eq!(
+            classify("ATGGCCGAACGTTTAGCCGGTACCGATTGA"),
+            vec![
+                CodingEffect {
+                    codon_index: 1,
+                    ref_aa: b'A',
+                    alt_aa: b'A',
+                    synonymous: true,
+                    frameshift: false,
+                },
+                CodingEffect {
+                    codon_index: 2,
+                    ref_aa: b'K',
+                    alt_aa: b'E',
+                    synonymous: false,
+                    frameshift: false,
+                },
+            ]
+        );
+        // 1 base insertion before the TTA codon
+        assert_eq!(
+            classify("ATGGCTAAACGTCTTAGCCGGTACCGATTGA"),
+            vec![CodingEffect {
+                codon_index: 4,
+                ref_aa: b'L',
+                alt_aa: b'X',
+                synonymous: false,
+                frameshift: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn value_types_compare_equal() {
+        let mut stats = MutationStats::default();
+        stats.inc_match();
+        stats.inc_substitution();
+        let mut same = MutationStats::default();
+        same.inc_substitution();
+        same.inc_match();
+        assert_eq!(stats, same);
+        same.inc_deletions();
+        assert_ne!(stats, same);
+
+        let gap = GapPanelty::new(-5, -1);
+        #[allow(clippy::clone_on_copy)]
+        let cloned = gap.clone();
+        assert_eq!(cloned, gap);
+        assert_eq!(Score::new(1, -1), Score::from((1, -1)));
+    }
+
+    #[test]
+    fn edit_script_of_known_alignment() {
+        //           GATTACACCGTAGG-CTAACGTTGCATTCGAAC
+        //           GATTACGCCGTAGGTCTAACGTTGCA---GAAC
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGGCTAACGTTGCATTCGAAC",
+            "GATTACGCCGTAGGTCTAACGTTGCAGAAC",
+            (-5, -1),
+            Score::new(1, -1),
+        );
+        diffstat.pairwise_aligner_global();
+
+        let ms = Muatation::from(&diffstat);
+        assert_eq!(ms.edit_script().unwrap(), "7A>G, 14_15insT, 26_28delTTC");
+    }
+
+    #[test]
+    fn edit_script_of_insertions_at_reference_ends() {
+        let edit_script = |query| {
+            let mut diffstat = DiffStat::new("GATTACACCG", query, (-5, -1), Score::new(1, -1));
+            diffstat.pairwise_aligner_global();
+            Muatation::from(&diffstat).edit_script().unwrap()
+        };
+
+        assert_eq!(edit_script("TTGATTACACCG"), "1insTT");
+        assert_eq!(edit_script("GATTACACCGAA"), "10insAA");
+    }
 }
@@ -0,0 +1,92 @@
+//! Machine readable serializers of [MutationReport]s, for the output formats of the CLI
+//! other than the `tabled` table
+
+use std::io::Write;
+
+use crate::mutation_detection::MutationReport;
+
+const HEADER: [&str; 8] = [
+    "query",
+    "reference",
+    "match",
+    "miss_match",
+    "substitution",
+    "insertions",
+    "deletions",
+    "total",
+];
+
+/// Write `reports` as delimiter separated values with a header row, e.g. `b','` for CSV or
+/// `b'\t'` for TSV. Fields are quoted when needed.
+pub fn write_delimited<W: Write>(
+    writer: W,
+    reports: &[MutationReport],
+    delimiter: u8,
+) -> csv::Result<()> {
+    tracing::info!("Writing {} reports as delimited values", reports.len());
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    writer.write_record(HEADER)?;
+    for report in reports {
+        let stats = report.stats();
+        let counts = [
+            stats.r#match(),
+            stats.miss_match(),
+            stats.substitution(),
+            stats.insertions(),
+            stats.deletions(),
+            stats.total(),
+        ];
+        writer.write_record(
+            [report.query().to_string(), report.reference().to_string()]
+                .into_iter()
+                .chain(counts.iter().map(usize::to_string)),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `reports` as a pretty printed JSON array. Requires the `serde` feature.
+#[cfg(feature = "serde")]
+pub fn write_json<W: Write>(mut writer: W, reports: &[MutationReport]) -> serde_json::Result<()> {
+    tracing::info!("Writing {} reports as JSON", reports.len());
+    serde_json::to_writer_pretty(&mut writer, reports)?;
+    writeln!(writer).map_err(serde_json::Error::io)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        aliner::{DiffStat, Score},
+        mutation_detection::{Muatation, MutationReport},
+    };
+
+    use super::write_delimited;
+
+    #[test]
+    fn write_tsv_with_header() {
+        let mut diffstat = DiffStat::new(
+            "GATTACACCGTAGG",
+            "GATTCCACCGTAGG",
+            (-5, -1),
+            Into::<Score>::into((1, -1)),
+        );
+        diffstat.pairwise_aligner_global();
+        let stats = Muatation::from(&diffstat).mutation_score().unwrap();
+        let reports = [MutationReport::new("query\t1", "ref", stats)];
+
+        let mut tsv = Vec::new();
+        write_delimited(&mut tsv, &reports, b'\t').unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        let mut lines = tsv.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("query\treference\tmatch\tmiss_match\tsubstitution\tinsertions\tdeletions\ttotal")
+        );
+        assert!(lines.next().unwrap().starts_with("\"query\t1\"\tref\t13\t"));
+        assert_eq!(lines.next(), None);
+    }
+}
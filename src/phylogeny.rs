@@ -0,0 +1,197 @@
+//! Pairwise distances between many sequences, the input of tree building and clustering
+
+use std::fmt::Display;
+
+use bio::alignment::{pairwise::MatchFunc, AlignmentOperation};
+
+use crate::aliner::{identity, DiffStat, GapPanelty};
+
+/// Symmetric matrix of pairwise distances (`1 - identity` of a global alignment), optionally
+/// with a confidence (`0.0..=1.0`) of every distance
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceMatrix {
+    len: usize,
+    /// Row major `len * len` distances
+    distances: Vec<f64>,
+    /// Row major `len * len` confidences, see [DistanceMatrix::with_confidence]
+    confidences: Option<Vec<f64>>,
+}
+
+impl DistanceMatrix {
+    /// Distances between every pair of `seqs`
+    pub fn new<S, F>(seqs: &[S], gap_penalty: &GapPanelty, score: &F) -> Self
+    where
+        S: AsRef<[u8]>,
+        F: MatchFunc + Clone + Display,
+    {
+        let mut matrix = Self::with_confidence(seqs, gap_penalty, score);
+        matrix.confidences = None;
+        matrix
+    }
+
+    /// Distances between every pair of `seqs`, also recording how much each distance can be
+    /// trusted: the identity times the coverage, i.e. the fraction of the longer sequence
+    /// aligned against a base of the other one.
+    pub fn with_confidence<S, F>(seqs: &[S], gap_penalty: &GapPanelty, score: &F) -> Self
+    where
+        S: AsRef<[u8]>,
+        F: MatchFunc + Clone + Display,
+    {
+        tracing::info!("Building distance matrix of {} sequences", seqs.len());
+        let len = seqs.len();
+        let mut distances = vec![0.0; len * len];
+        let mut confidences = vec![1.0; len * len];
+        for i in 0..len {
+            for j in i + 1..len {
+                let (a, b) = (seqs[i].as_ref(), seqs[j].as_ref());
+                let mut diff =
+                    DiffStat::new(a, b, (gap_penalty.open, gap_penalty.extend), score.clone());
+                diff.pairwise_aligner_global();
+                let alignment = diff.alignment().expect("Alignment was performed");
+
+                let aligned = alignment
+                    .operations
+                    .iter()
+                    .filter(|operation| {
+                        matches!(
+                            operation,
+                            AlignmentOperation::Match | AlignmentOperation::Subst
+                        )
+                    })
+                    .count();
+                let longest = a.len().max(b.len());
+                let coverage = if longest == 0 {
+                    0.0
+                } else {
+                    aligned as f64 / longest as f64
+                };
+                let identity = identity(alignment);
+
+                for (row, col) in [(i, j), (j, i)] {
+                    distances[row * len + col] = 1.0 - identity;
+                    confidences[row * len + col] = identity * coverage;
+                }
+            }
+        }
+        Self {
+            len,
+            distances,
+            confidences: Some(confidences),
+        }
+    }
+
+    /// Number of sequences
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Distance between sequences `i` and `j`
+    pub fn distance(&self, i: usize, j: usize) -> f64 {
+        self.distances[i * self.len + j]
+    }
+
+    /// Identity (`0.0..=1.0`) of sequences `i` and `j`
+    pub fn identity(&self, i: usize, j: usize) -> f64 {
+        1.0 - self.distance(i, j)
+    }
+
+    /// Confidence of the distance between `i` and `j`, `None` unless built
+    /// [DistanceMatrix::with_confidence]
+    pub fn confidence(&self, i: usize, j: usize) -> Option<f64> {
+        self.confidences
+            .as_ref()
+            .map(|confidences| confidences[i * self.len + j])
+    }
+
+    /// Distance between `i` and `j` along with its weight, the confidence or `1.0` when it
+    /// was not recorded
+    pub fn weighted(&self, i: usize, j: usize) -> (f64, f64) {
+        (self.distance(i, j), self.confidence(i, j).unwrap_or(1.0))
+    }
+}
+
+/// Single-linkage hierarchical clustering: sequences closer than `threshold` end up in the
+/// same cluster, as do clusters with any such pair between them.
+///
+/// Returns the clusters as sorted sequence indices, ordered by their first index.
+pub fn cluster(matrix: &DistanceMatrix, threshold: f64) -> Vec<Vec<usize>> {
+    tracing::info!("Clustering {} sequences below {}", matrix.len(), threshold);
+    // Union-find over the sequences, every cluster pointing to its lowest index
+    let mut parent = (0..matrix.len()).collect::<Vec<_>>();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..matrix.len() {
+        for j in i + 1..matrix.len() {
+            if matrix.distance(i, j) < threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of = vec![usize::MAX; matrix.len()];
+    for i in 0..matrix.len() {
+        let root = root(&mut parent, i);
+        if cluster_of[root] == usize::MAX {
+            cluster_of[root] = clusters.len();
+            clusters.push(Vec::new());
+        }
+        clusters[cluster_of[root]].push(i);
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::{GapPanelty, Score};
+
+    use super::{cluster, DistanceMatrix};
+
+    #[test]
+    fn poor_coverage_lowers_confidence() {
+        let seqs = [
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "GATTACACCGTAGGCTAACGTTAGCCATGGTCT",
+            "GATTACACCGTA",
+        ];
+        let gap = GapPanelty::new(-5, -1);
+        let score = Score::new(1, -1);
+
+        let plain = DistanceMatrix::new(&seqs, &gap, &score);
+        assert_eq!(plain.confidence(0, 1), None);
+        assert_eq!(plain.weighted(0, 1), (plain.distance(0, 1), 1.0));
+
+        let matrix = DistanceMatrix::with_confidence(&seqs, &gap, &score);
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix.distance(0, 1), matrix.distance(1, 0));
+        assert_eq!(matrix.distance(0, 0), 0.0);
+        let well_covered = matrix.confidence(0, 1).unwrap();
+        let poorly_covered = matrix.confidence(0, 2).unwrap();
+        assert!(well_covered > 0.9);
+        assert!(poorly_covered < well_covered);
+    }
+
+    #[test]
+    fn cluster_near_identical() {
+        let seqs = [
+            "GATTACACCGTAGGCTAACGTTAGCCATGGACT",
+            "CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAA",
+            "GATTACACCGTAGGCTAACGTTAGCCATGGTCT",
+        ];
+        let matrix = DistanceMatrix::new(&seqs, &GapPanelty::new(-5, -1), &Score::new(1, -1));
+
+        assert!(matrix.identity(0, 2) > 0.95);
+        assert_eq!(cluster(&matrix, 0.1), vec![vec![0, 2], vec![1]]);
+        assert_eq!(cluster(&matrix, 0.0), vec![vec![0], vec![1], vec![2]]);
+    }
+}
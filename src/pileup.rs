@@ -0,0 +1,109 @@
+//! Pileup of many queries aligned against one reference, and its majority vote consensus
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use bio::alignment::{pairwise::MatchFunc, AlignmentOperation};
+
+use crate::aliner::{alignment_columns, DiffStat};
+
+/// Base counts at every reference position over many alignments sharing the same reference.
+///
+/// Only bases aligned to a reference base (matches and substitutions) are counted, deletions
+/// and insertions don't add to the depth.
+#[derive(Debug, Clone)]
+pub struct Pileup {
+    reference: Vec<u8>,
+    counts: Vec<BTreeMap<u8, usize>>,
+}
+
+impl Pileup {
+    pub fn new(reference: &[u8]) -> Self {
+        Self {
+            reference: reference.to_vec(),
+            counts: vec![BTreeMap::new(); reference.len()],
+        }
+    }
+
+    /// Add the query bases of one more alignment, counted case insensitively.
+    ///
+    /// Panics if `diffstat` is not aligned or has another reference.
+    pub fn add<F>(&mut self, diffstat: &DiffStat<F>)
+    where
+        F: MatchFunc + Clone + Display,
+    {
+        assert!(
+            diffstat.reference() == self.reference,
+            "Alignment has a different reference than the pileup"
+        );
+        let alignment = diffstat
+            .alignment()
+            .expect("DiffStat is not aligned, please use pairwise alignment before adding it");
+        let query = diffstat.query();
+        for (operation, ref_pos, query_pos) in alignment_columns(alignment) {
+            if matches!(
+                operation,
+                AlignmentOperation::Match | AlignmentOperation::Subst
+            ) {
+                *self.counts[ref_pos]
+                    .entry(query[query_pos].to_ascii_uppercase())
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Count of every base seen at each reference position
+    pub fn base_counts(&self) -> &[BTreeMap<u8, usize>] {
+        &self.counts
+    }
+
+    /// Majority base at every reference position, `N` where fewer than `min_depth` bases were
+    /// counted. Ties go to the alphabetically first base.
+    pub fn consensus(&self, min_depth: usize) -> Vec<u8> {
+        tracing::info!("Calling pileup consensus with min depth {}", min_depth);
+        self.counts
+            .iter()
+            .map(|counts| {
+                let depth = counts.values().sum::<usize>();
+                if depth == 0 || depth < min_depth {
+                    return b'N';
+                }
+                counts
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, count)| **count)
+                    .map_or(b'N', |(base, _)| *base)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::{DiffStat, Score};
+
+    use super::Pileup;
+
+    #[test]
+    fn majority_base_of_three_alignments() {
+        let reference = b"GATTAAACCGTAGGCT".as_slice();
+        let queries = [
+            b"GATTAAACCGTAGGCT".as_slice(),
+            b"GATTAGACCGTAGGCT".as_slice(),
+            b"gattaaaccgtaggct".as_slice(),
+        ];
+        let mut pileup = Pileup::new(reference);
+        for query in queries {
+            let mut diffstat = DiffStat::new(reference, query, (-5, -1), Score::new(1, -1));
+            diffstat.pairwise_aligner_global();
+            pileup.add(&diffstat);
+        }
+
+        let at_five = &pileup.base_counts()[5];
+        assert_eq!(at_five.get(&b'A'), Some(&2));
+        assert_eq!(at_five.get(&b'G'), Some(&1));
+        let consensus = pileup.consensus(3);
+        assert_eq!(consensus[5], b'A');
+        assert_eq!(consensus, reference);
+        assert_eq!(pileup.consensus(4), vec![b'N'; reference.len()]);
+    }
+}
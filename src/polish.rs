@@ -0,0 +1,124 @@
+//! Polishing of a draft reference with aligned reads
+
+use std::fmt::Display;
+
+use bio::alignment::{pairwise::MatchFunc, AlignmentOperation};
+
+use crate::{
+    aliner::{alignment_columns, local_alignment, GapPanelty},
+    sequence::PHRED_OFFSET,
+};
+
+/// Summed Phred quality a base needs in the pileup to replace the reference base, e.g. two
+/// Q20 reads or a single Q40 one
+pub const MIN_SUPPORT: u32 = 40;
+
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// Correct substitutions in a draft `reference` using `(sequence, quality)` reads, with
+/// Phred+33 encoded qualities as in FASTQ.
+///
+/// Every read is locally aligned to the reference and its aligned bases are piled up,
+/// weighted by their quality. A reference base is replaced when another base wins the pileup
+/// with a summed quality of at least [MIN_SUPPORT]. Indels are not corrected.
+pub fn polish<F>(
+    reference: &[u8],
+    reads: &[(&[u8], &[u8])],
+    gap_penalty: &GapPanelty,
+    score: &F,
+) -> Vec<u8>
+where
+    F: MatchFunc + Clone + Display,
+{
+    tracing::info!("Polishing reference with {} reads", reads.len());
+    let mut pileup = vec![[0u32; 4]; reference.len()];
+    for (seq, qual) in reads {
+        let Some(alignment) = local_alignment(seq, reference, gap_penalty, score) else {
+            continue;
+        };
+        for (operation, ref_pos, query_pos) in alignment_columns(&alignment) {
+            if !matches!(
+                operation,
+                AlignmentOperation::Match | AlignmentOperation::Subst
+            ) {
+                continue;
+            }
+            if let Some(idx) = base_index(seq[query_pos]) {
+                let quality = qual
+                    .get(query_pos)
+                    .map_or(0, |q| q.saturating_sub(PHRED_OFFSET));
+                pileup[ref_pos][idx] += quality as u32;
+            }
+        }
+    }
+
+    reference
+        .iter()
+        .zip(&pileup)
+        .map(|(&base, weights)| {
+            let (best, &support) = weights
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, weight)| **weight)
+                .expect("Pileup has a weight per base");
+            if support >= MIN_SUPPORT && base_index(base) != Some(best) {
+                b"ACGT"[best]
+            } else {
+                base
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::{GapPanelty, Score};
+
+    use super::polish;
+
+    const REFERENCE: &[u8] = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGAATTAG";
+
+    #[test]
+    fn polish_supported_correction() {
+        // Reads agree on a T at 20 instead of the A of the draft
+        let mut read = REFERENCE[5..45].to_vec();
+        read[15] = b'T';
+        let qual = vec![b'?'; read.len()]; // Q30
+        let reads = vec![(read.as_slice(), qual.as_slice()); 3];
+
+        let polished = polish(
+            REFERENCE,
+            &reads,
+            &GapPanelty::new(-5, -1),
+            &Score::new(1, -1),
+        );
+
+        let mut expected = REFERENCE.to_vec();
+        expected[20] = b'T';
+        assert_eq!(polished, expected);
+    }
+
+    #[test]
+    fn polish_ignores_single_low_quality_disagreement() {
+        let mut read = REFERENCE[5..45].to_vec();
+        read[15] = b'T';
+        let qual = vec![b'+'; read.len()]; // Q10
+
+        let polished = polish(
+            REFERENCE,
+            &[(read.as_slice(), qual.as_slice())],
+            &GapPanelty::new(-5, -1),
+            &Score::new(1, -1),
+        );
+
+        assert_eq!(polished, REFERENCE);
+    }
+}
@@ -0,0 +1,156 @@
+//! Streaming quantiles of identity values, for batch runs too large to keep every value
+
+/// Number of equiprobable cells tracked by [IdentityQuantiles], the more the more accurate
+const CELLS: usize = 32;
+
+/// Running quantiles of identities (`0.0..=1.0`) using the
+/// [P² algorithm](https://www.cse.wustl.edu/~jain/papers/ftp/psqr.pdf) for histograms.
+///
+/// Keeps `CELLS + 1` markers at evenly spaced quantiles whose heights are adjusted with a
+/// piecewise parabolic interpolation on every [IdentityQuantiles::add], so memory stays
+/// constant however many values are added.
+#[derive(Debug, Clone)]
+pub struct IdentityQuantiles {
+    /// Marker heights, i.e. the estimated quantiles
+    heights: Vec<f64>,
+    /// Actual (1-based) marker positions
+    positions: Vec<f64>,
+    count: usize,
+}
+
+impl Default for IdentityQuantiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdentityQuantiles {
+    pub fn new() -> Self {
+        Self {
+            heights: Vec::with_capacity(CELLS + 1),
+            positions: (1..=CELLS + 1).map(|position| position as f64).collect(),
+            count: 0,
+        }
+    }
+
+    /// Number of added values
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn add(&mut self, identity: f64) {
+        self.count += 1;
+        // Exact until there is a value for every marker
+        if self.heights.len() <= CELLS {
+            let idx = self.heights.partition_point(|height| *height <= identity);
+            self.heights.insert(idx, identity);
+            return;
+        }
+
+        let last = CELLS;
+        let cell = if identity < self.heights[0] {
+            self.heights[0] = identity;
+            0
+        } else if identity >= self.heights[last] {
+            self.heights[last] = identity;
+            last - 1
+        } else {
+            self.heights.partition_point(|height| *height <= identity) - 1
+        };
+        self.positions[cell + 1..]
+            .iter_mut()
+            .for_each(|position| *position += 1.0);
+
+        for i in 1..last {
+            let desired = 1.0 + (self.count - 1) as f64 * i as f64 / CELLS as f64;
+            let delta = desired - self.positions[i];
+            let (left, right) = (
+                self.positions[i - 1] - self.positions[i],
+                self.positions[i + 1] - self.positions[i],
+            );
+            if (delta >= 1.0 && right > 1.0) || (delta <= -1.0 && left < -1.0) {
+                let step = delta.signum();
+                let parabolic = self.parabolic(i, step);
+                self.heights[i] =
+                    if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                        parabolic
+                    } else {
+                        self.linear(i, step)
+                    };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    /// Piecewise parabolic prediction of the height of marker `i` moved by `step` (`±1`)
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    /// Linear prediction of the height of marker `i` moved by `step` (`±1`)
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let neighbour = if step > 0.0 { i + 1 } else { i - 1 };
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step * (q[neighbour] - q[i]) / (n[neighbour] - n[i])
+    }
+
+    /// Estimated `q` quantile (`0.0..=1.0`), e.g. `0.5` for the median. `NaN` if no value
+    /// has been added.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.heights.is_empty() {
+            return f64::NAN;
+        }
+        let q = q.clamp(0.0, 1.0);
+        // While exact, markers are the sorted values at positions 1..=count
+        let positions = if self.count <= CELLS {
+            &self.positions[..self.count]
+        } else {
+            &self.positions[..]
+        };
+        if positions.len() == 1 {
+            return self.heights[0];
+        }
+        let target = 1.0 + (self.count - 1) as f64 * q;
+        let upper = positions
+            .partition_point(|position| *position < target)
+            .clamp(1, positions.len() - 1);
+        let lower = upper - 1;
+        let fraction = (target - positions[lower]) / (positions[upper] - positions[lower]);
+        self.heights[lower] + fraction * (self.heights[upper] - self.heights[lower])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdentityQuantiles;
+
+    #[test]
+    fn approximate_median_and_p90() {
+        // Shuffled 0.0..1.0 in steps of 0.0001
+        let values = (0..10_000u64)
+            .map(|i| (i * 7919 % 10_000) as f64 / 10_000.0)
+            .collect::<Vec<_>>();
+        let mut quantiles = IdentityQuantiles::new();
+        values.iter().for_each(|value| quantiles.add(*value));
+
+        assert_eq!(quantiles.count(), 10_000);
+        assert!((quantiles.quantile(0.5) - 0.5).abs() < 0.01);
+        assert!((quantiles.quantile(0.9) - 0.9).abs() < 0.01);
+    }
+
+    #[test]
+    fn exact_for_few_values() {
+        let mut quantiles = IdentityQuantiles::new();
+        assert!(quantiles.quantile(0.5).is_nan());
+
+        [0.9, 0.7, 0.8]
+            .iter()
+            .for_each(|value| quantiles.add(*value));
+        assert_eq!(quantiles.quantile(0.5), 0.8);
+        assert_eq!(quantiles.quantile(0.0), 0.7);
+        assert_eq!(quantiles.quantile(1.0), 0.9);
+    }
+}
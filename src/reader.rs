@@ -1,16 +1,109 @@
-use std::{fmt::Debug, fs::File, io::BufReader, path::Path};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use bio::io::fasta::{Record, Records};
+use bio::io::{
+    fasta::{self, Record, Records},
+    fastq,
+};
+use flate2::read::MultiGzDecoder;
+
+use crate::sequence::sequence_hash;
 
 #[derive(Debug, thiserror::Error)]
 pub enum FastaReaderError {
     #[error("Error: {0}")]
     Generic(String),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("File not found: {0:?}")]
+    NotFound(PathBuf),
+    #[error("Malformed FASTA at line {line}: {msg}")]
+    Parse { line: usize, msg: String },
+    #[error("FASTA input contains no record")]
+    Empty,
+    #[error("FASTA input contains {0} records, expected one")]
+    MultipleRecords(usize),
+}
+
+impl FastaReaderError {
+    /// Error of the bio FASTA parser, raised while reading `line` (1-based). IO errors other
+    /// than invalid content are kept as is.
+    fn from_record(err: io::Error, line: usize) -> Self {
+        match err.kind() {
+            io::ErrorKind::Other | io::ErrorKind::InvalidData => Self::Parse {
+                line,
+                msg: err.to_string(),
+            },
+            _ => Self::Io(err),
+        }
+    }
 }
 
+/// Buffered reader counting the lines consumed, to locate parse errors
 #[derive(Debug)]
-pub struct FastaReader {
-    inner: Records<BufReader<File>>,
+pub struct LineCounter<B> {
+    inner: B,
+    /// 1-based line of the last consumed byte, shared with the [FastaReader]
+    line: Arc<AtomicUsize>,
+    at_line_start: bool,
+}
+
+impl<B: BufRead> Read for LineCounter<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<B: BufRead> BufRead for LineCounter<B> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            let mut started = 0;
+            for byte in &buf[..amt.min(buf.len())] {
+                started += self.at_line_start as usize;
+                self.at_line_start = *byte == b'\n';
+            }
+            self.line.fetch_add(started, Ordering::Relaxed);
+        }
+        self.inner.consume(amt)
+    }
+}
+
+#[derive(Debug)]
+pub struct FastaReader<R = File>
+where
+    R: Read,
+{
+    inner: Records<LineCounter<BufReader<R>>>,
+    /// Line being parsed, see [LineCounter]
+    line: Arc<AtomicUsize>,
+}
+
+/// Open a FASTA file, telling a missing file apart from other IO errors
+fn open_file<P>(file_path: P) -> Result<File, FastaReaderError>
+where
+    P: AsRef<Path>,
+{
+    File::open(&file_path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => FastaReaderError::NotFound(file_path.as_ref().to_path_buf()),
+        _ => FastaReaderError::Io(err),
+    })
 }
 
 impl FastaReader {
@@ -19,36 +112,657 @@ impl FastaReader {
         P: AsRef<Path> + Debug,
     {
         tracing::info!("Fasta reader for file {:?}", file_path);
-        let fasta_reader = bio::io::fasta::Reader::from_file(file_path)
-            .map_err(|err| FastaReaderError::Generic(err.to_string()))?;
-        Ok(Self {
-            inner: fasta_reader.records(),
-        })
+        let file = open_file(&file_path)?;
+        Self::from_reader(file)
     }
+}
+
+/// Magic bytes starting every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-    pub fn records(self) -> Records<BufReader<File>> {
+impl FastaReader<Box<dyn Read>> {
+    /// Fasta reader for a gzip compressed file, e.g. `.fasta.gz`.
+    ///
+    /// Compression is detected from the gzip magic bytes, not the file name, so a plain FASTA
+    /// file is read as is.
+    pub fn from_gzip_file<P>(file_path: P) -> Result<Self, FastaReaderError>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        tracing::info!("Fasta reader for gzip file {:?}", file_path);
+        let mut file = BufReader::new(open_file(&file_path)?);
+        let gzipped = file.fill_buf()?.starts_with(&GZIP_MAGIC);
+        let reader: Box<dyn Read> = if gzipped {
+            Box::new(MultiGzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Self::from_reader(reader)
+    }
+}
+
+impl<'a> FastaReader<&'a [u8]> {
+    /// Parse FASTA records held in memory
+    ///
+    /// Not an implementation of [std::str::FromStr], as the reader borrows `data` instead of copying it
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(data: &'a str) -> Self {
+        Self::from_bytes(data.as_bytes())
+    }
+
+    /// Parse FASTA records held in memory
+    pub fn from_bytes(data: &'a [u8]) -> Self {
+        tracing::info!("Fasta reader for {} in-memory bytes", data.len());
+        Self::new(data)
+    }
+}
+
+impl<R> FastaReader<R>
+where
+    R: Read,
+{
+    fn new(reader: R) -> Self {
+        let line = Arc::new(AtomicUsize::new(0));
+        let counter = LineCounter {
+            inner: BufReader::new(reader),
+            line: line.clone(),
+            at_line_start: true,
+        };
+        Self {
+            inner: fasta::Reader::from_bufread(counter).records(),
+            line,
+        }
+    }
+
+    /// Parse FASTA records from any reader, e.g. stdin or a network stream
+    pub fn from_reader(reader: R) -> Result<Self, FastaReaderError> {
+        tracing::info!("Fasta reader for generic reader");
+        Ok(Self::new(reader))
+    }
+
+    pub fn records(self) -> Records<LineCounter<BufReader<R>>> {
         self.inner
     }
+
+    /// Every record, failing on the first one that can't be parsed
+    pub fn all_records(self) -> Result<Vec<Record>, FastaReaderError> {
+        let line = self.line;
+        self.inner
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| FastaReaderError::from_record(err, line.load(Ordering::Relaxed)))
+    }
+
+    /// The only record, failing if there is none or more than one
+    pub fn single_record(self) -> Result<Record, FastaReaderError> {
+        let mut records = self.all_records()?;
+        match records.len() {
+            1 => Ok(records.remove(0)),
+            0 => Err(FastaReaderError::Empty),
+            len => Err(FastaReaderError::MultipleRecords(len)),
+        }
+    }
 }
 
-impl IntoIterator for FastaReader {
+impl<R> IntoIterator for FastaReader<R>
+where
+    R: Read,
+{
     type Item = Record;
 
-    type IntoIter = FastaReaderIter;
+    type IntoIter = FastaReaderIter<R>;
 
     fn into_iter(self) -> Self::IntoIter {
-        todo!()
+        FastaReaderIter { inner: self.inner }
     }
 }
 
-pub struct FastaReaderIter {
-    inner: Records<BufReader<File>>,
+/// Iterator over the records of a [FastaReader], records failing to parse are skipped
+pub struct FastaReaderIter<R = File>
+where
+    R: Read,
+{
+    inner: Records<LineCounter<BufReader<R>>>,
 }
 
-impl Iterator for FastaReaderIter {
+impl<R> Iterator for FastaReaderIter<R>
+where
+    R: Read,
+{
     type Item = Record;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next().and_then(|rec| rec.ok())
     }
 }
+
+/// Default number of bases per line written by [FastaWriter]
+pub const DEFAULT_LINE_WIDTH: usize = 70;
+
+/// Writer of FASTA records, wrapping sequences to a fixed number of bases per line
+#[derive(Debug)]
+pub struct FastaWriter<W>
+where
+    W: Write,
+{
+    inner: fasta::Writer<W>,
+    line_width: usize,
+}
+
+impl FastaWriter<File> {
+    /// Create (or truncate) `file_path` and write FASTA records into it
+    pub fn from_file<P>(file_path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        tracing::info!("Fasta writer for file {:?}", file_path);
+        Ok(Self::new(File::create(file_path)?))
+    }
+}
+
+impl<W> FastaWriter<W>
+where
+    W: Write,
+{
+    /// Buffered writer of FASTA records into `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: fasta::Writer::new(writer),
+            line_width: DEFAULT_LINE_WIDTH,
+        }
+    }
+
+    /// Wrap sequences after `line_width` bases, `0` writes every sequence on a single line
+    pub fn with_line_width(mut self, line_width: usize) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn write_record(&mut self, id: &str, desc: Option<&str>, seq: &[u8]) -> io::Result<()> {
+        if self.line_width == 0 || seq.len() <= self.line_width {
+            return self.inner.write(id, desc, seq);
+        }
+        let wrapped = seq.chunks(self.line_width).collect::<Vec<_>>().join(&b'\n');
+        self.inner.write(id, desc, &wrapped)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Build the `.fai` index of a FASTA file, as `samtools faidx` does: one line of name,
+/// length, offset of the first base, bases per line and bytes per line for every record.
+///
+/// Every line of a record but the last must have the same length.
+fn build_fai<R: BufRead>(mut reader: R) -> Result<String, FastaReaderError> {
+    // (name, length, offset, line bases, line width) of the record being read
+    let mut entries: Vec<(String, usize, usize, usize, usize)> = Vec::new();
+    // Whether a line shorter than the first one ended the current record
+    let mut record_ended = false;
+    let mut offset = 0;
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        let width = reader.read_until(b'\n', &mut line)?;
+        if width == 0 {
+            break;
+        }
+        line_number += 1;
+        offset += width;
+        let content = line.trim_ascii_end();
+        if let Some(header) = content.strip_prefix(b">") {
+            let name = String::from_utf8_lossy(header)
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            entries.push((name, 0, offset, 0, 0));
+            record_ended = false;
+            continue;
+        }
+        let Some((_, length, _, line_bases, line_width)) = entries.last_mut() else {
+            return Err(FastaReaderError::Parse {
+                line: line_number,
+                msg: "Expected > at record start.".to_string(),
+            });
+        };
+        if content.is_empty() {
+            continue;
+        }
+        if *line_bases == 0 {
+            (*line_bases, *line_width) = (content.len(), width);
+        } else if record_ended || content.len() > *line_bases {
+            return Err(FastaReaderError::Parse {
+                line: line_number,
+                msg: "Lines of a record must have the same length to be indexed".to_string(),
+            });
+        }
+        record_ended = content.len() < *line_bases;
+        *length += content.len();
+    }
+    Ok(entries
+        .into_iter()
+        .map(|(name, length, offset, line_bases, line_width)| {
+            format!("{name}\t{length}\t{offset}\t{line_bases}\t{line_width}\n")
+        })
+        .collect())
+}
+
+/// Random access to the records of a FASTA file through its `.fai` index
+#[derive(Debug)]
+pub struct IndexedFastaReader {
+    inner: fasta::IndexedReader<File>,
+    /// Length of every record by name
+    lengths: HashMap<String, u64>,
+}
+
+impl IndexedFastaReader {
+    /// Open `file_path` with the index at `file_path.fai`. A missing index is built, and saved
+    /// next to the file when possible.
+    pub fn from_file<P>(file_path: P) -> Result<Self, FastaReaderError>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        tracing::info!("Indexed Fasta reader for file {:?}", file_path);
+        let file = open_file(&file_path)?;
+        let mut fai_path = file_path.as_ref().as_os_str().to_owned();
+        fai_path.push(".fai");
+        let fai = match fs::read(&fai_path) {
+            Ok(fai) => fai,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                tracing::info!("Building missing index {:?}", fai_path);
+                let fai = build_fai(BufReader::new(File::open(&file_path)?))?;
+                if let Err(err) = fs::write(&fai_path, &fai) {
+                    tracing::warn!("Unable to save index {:?}: {}", fai_path, err);
+                }
+                fai.into_bytes()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let index = fasta::Index::new(fai.as_slice())
+            .map_err(|err| FastaReaderError::Generic(format!("Invalid FASTA index: {err}")))?;
+        let lengths = index
+            .sequences()
+            .into_iter()
+            .map(|sequence| (sequence.name, sequence.len))
+            .collect();
+        Ok(Self {
+            inner: fasta::IndexedReader::with_index(file, index),
+            lengths,
+        })
+    }
+
+    /// Bases `start..end` (0-based, half-open) of record `name`, clipped to its end
+    pub fn fetch(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, FastaReaderError> {
+        tracing::info!("Fetching {}:{}-{}", name, start, end);
+        let end = self.lengths.get(name).map_or(end, |len| end.min(*len));
+        self.inner.fetch(name, start.min(end), end)?;
+        let mut seq = Vec::new();
+        self.inner.read(&mut seq)?;
+        Ok(seq)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FastqReaderError {
+    #[error("Error: {0}")]
+    Generic(String),
+}
+
+#[derive(Debug)]
+pub struct FastqReader<R = File>
+where
+    R: Read,
+{
+    inner: fastq::Records<BufReader<R>>,
+}
+
+impl FastqReader {
+    pub fn from_file<P>(file_path: P) -> Result<Self, FastqReaderError>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        tracing::info!("Fastq reader for file {:?}", file_path);
+        let fastq_reader = fastq::Reader::from_file(file_path)
+            .map_err(|err| FastqReaderError::Generic(err.to_string()))?;
+        Ok(Self {
+            inner: fastq_reader.records(),
+        })
+    }
+}
+
+impl<R> FastqReader<R>
+where
+    R: Read,
+{
+    /// Parse FASTQ records from any reader
+    pub fn from_reader(reader: R) -> Result<Self, FastqReaderError> {
+        tracing::info!("Fastq reader for generic reader");
+        Ok(Self {
+            inner: fastq::Reader::new(reader).records(),
+        })
+    }
+
+    pub fn records(self) -> fastq::Records<BufReader<R>> {
+        self.inner
+    }
+}
+
+/// Reader of records holding an ID and a sequence, so FASTA and FASTQ input can be handled
+/// alike
+pub trait SequenceReader {
+    /// `(id, sequence)` of every record, records failing to parse are skipped
+    fn sequences(self) -> impl Iterator<Item = (String, Vec<u8>)>;
+}
+
+impl<R> SequenceReader for FastaReader<R>
+where
+    R: Read,
+{
+    fn sequences(self) -> impl Iterator<Item = (String, Vec<u8>)> {
+        self.into_iter()
+            .map(|record| (record.id().to_string(), record.seq().to_vec()))
+    }
+}
+
+impl<R> SequenceReader for FastqReader<R>
+where
+    R: Read,
+{
+    fn sequences(self) -> impl Iterator<Item = (String, Vec<u8>)> {
+        self.inner
+            .filter_map(|record| record.ok())
+            .map(|record| (record.id().to_string(), record.seq().to_vec()))
+    }
+}
+
+/// Extra information on FASTA records
+pub trait RecordExt {
+    /// See [sequence_hash]
+    fn sequence_hash(&self) -> String;
+}
+
+impl RecordExt for Record {
+    fn sequence_hash(&self) -> String {
+        sequence_hash(self.seq())
+    }
+}
+
+/// File name extensions of FASTA files read by [read_directory]
+const FASTA_EXTENSIONS: [&str; 3] = [".fasta", ".fa", ".fasta.gz"];
+
+/// Read every record of the `.fasta`, `.fa` and `.fasta.gz` files of a directory (not
+/// recursively) as `(id, sequence)`, other files are skipped. Files are read in name order.
+///
+/// A file holding a single record without an ID gets the file name, minus its extension, as
+/// ID.
+pub fn read_directory<P>(dir: P) -> Result<Vec<(String, Vec<u8>)>, FastaReaderError>
+where
+    P: AsRef<Path> + Debug,
+{
+    tracing::info!("Reading FASTA files of directory {:?}", dir);
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            let stem = FASTA_EXTENSIONS
+                .iter()
+                .find_map(|extension| name.strip_suffix(extension))?
+                .to_string();
+            Some((name, stem, path))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut sequences = Vec::new();
+    for (_, stem, path) in files {
+        let records = FastaReader::from_gzip_file(&path)?.all_records()?;
+        let unnamed = records.len() == 1 && records[0].id().is_empty();
+        sequences.extend(records.into_iter().map(|record| {
+            let id = if unnamed {
+                stem.clone()
+            } else {
+                record.id().to_string()
+            };
+            (id, record.seq().to_vec())
+        }));
+    }
+    Ok(sequences)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{Cursor, Write},
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::{
+        read_directory, FastaReader, FastaReaderError, FastaWriter, FastqReader,
+        IndexedFastaReader, RecordExt, SequenceReader,
+    };
+
+    #[test]
+    fn read_from_str() {
+        let data = ">seq1 first\nACGT\nACGT\n>seq2\nTTGA\n";
+        let records = FastaReader::from_str(data)
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Unable to parse in-memory FASTA");
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id(), "seq1");
+        assert_eq!(records[0].desc(), Some("first"));
+        assert_eq!(records[0].seq(), b"ACGTACGT");
+        assert_eq!(records[1].id(), "seq2");
+        assert_eq!(records[1].seq(), b"TTGA");
+        assert_ne!(records[0].sequence_hash(), records[1].sequence_hash());
+    }
+
+    #[test]
+    fn read_fasta_directory() {
+        let dir = std::env::temp_dir().join(format!("dna-read-directory-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("unnamed.fa"), ">\nACGT\n").unwrap();
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped.write_all(b">seq1\nTTGA\n>seq2\nCCAT\n").unwrap();
+        fs::write(dir.join("pair.fasta.gz"), gzipped.finish().unwrap()).unwrap();
+        fs::write(dir.join("notes.txt"), "not a FASTA file").unwrap();
+
+        let sequences = read_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            sequences.unwrap(),
+            vec![
+                ("seq1".to_string(), b"TTGA".to_vec()),
+                ("seq2".to_string(), b"CCAT".to_vec()),
+                ("unnamed".to_string(), b"ACGT".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn iterate_file_records() {
+        let path = std::env::temp_dir().join(format!("dna-iterate-{}.fasta", std::process::id()));
+        fs::write(&path, ">seq1\nACGT\n>seq2\nTTGA\n>seq3\nCCAT\n").unwrap();
+
+        let reader = FastaReader::from_file(&path);
+        let mut ids = Vec::new();
+        for record in reader.unwrap() {
+            ids.push(record.id().to_string());
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ids, ["seq1", "seq2", "seq3"]);
+    }
+
+    #[test]
+    fn read_from_reader() {
+        let data = b">seq1\nACGT\n>seq2\nTTGA\n".to_vec();
+        let records = FastaReader::from_reader(Cursor::new(data))
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id(), "seq1");
+        assert_eq!(records[1].seq(), b"TTGA");
+    }
+
+    #[test]
+    fn read_gzip_file() {
+        let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+        let plain = FastaReader::from_file(format!("{data}/reference.fasta"))
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+        let gzipped = FastaReader::from_gzip_file(format!("{data}/reference.fasta.gz"))
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(gzipped.id(), plain.id());
+        assert_eq!(gzipped.seq(), plain.seq());
+
+        // No magic bytes, read as plain FASTA
+        let misnamed = FastaReader::from_gzip_file(format!("{data}/reference.fasta"))
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(misnamed.seq(), plain.seq());
+    }
+
+    #[test]
+    fn read_fastq_file() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/reads.fastq");
+        let records = FastqReader::from_file(path)
+            .unwrap()
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Unable to parse FASTQ");
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id(), "read1");
+        for record in &records {
+            assert_eq!(record.qual().len(), record.seq().len());
+        }
+
+        let sequences = FastqReader::from_file(path)
+            .unwrap()
+            .sequences()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sequences[1],
+            ("read2".to_string(), b"CCGTAATGCCTTTCCC".to_vec())
+        );
+    }
+
+    #[test]
+    fn single_record_of_input() {
+        assert!(matches!(
+            FastaReader::from_str("").single_record(),
+            Err(FastaReaderError::Empty)
+        ));
+        assert!(FastaReader::from_str("").all_records().unwrap().is_empty());
+
+        let record = FastaReader::from_str(">seq1\nACGT\n")
+            .single_record()
+            .unwrap();
+        assert_eq!(record.seq(), b"ACGT");
+
+        let two = ">seq1\nACGT\n>seq2\nTTGA\n";
+        assert!(matches!(
+            FastaReader::from_str(two).single_record(),
+            Err(FastaReaderError::MultipleRecords(2))
+        ));
+        assert_eq!(FastaReader::from_str(two).all_records().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn structured_reader_errors() {
+        let missing = FastaReader::from_file("/nonexistent/reference.fasta");
+        assert!(
+            matches!(missing, Err(FastaReaderError::NotFound(path)) if path.ends_with("reference.fasta"))
+        );
+        let missing = FastaReader::from_gzip_file("/nonexistent/reference.fasta.gz");
+        assert!(
+            matches!(missing, Err(FastaReaderError::NotFound(path)) if path.ends_with("reference.fasta.gz"))
+        );
+
+        let malformed = FastaReader::from_str("ACGT\n>seq1\nACGT\n").all_records();
+        assert!(matches!(
+            malformed,
+            Err(FastaReaderError::Parse { line: 1, .. })
+        ));
+
+        // Invalid UTF-8 in the sequence lines of the second record
+        let data = b">seq1\nACGT\n>seq2\nAC\nG\xffT\n";
+        let invalid = FastaReader::from_bytes(data).all_records();
+        assert!(matches!(
+            invalid,
+            Err(FastaReaderError::Parse { line: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn write_and_read_back_records() {
+        let long = b"GATTACACCGTAGGCTAACGTTAGCCATGGACT".repeat(3);
+        let mut written = Vec::new();
+        let mut writer = FastaWriter::new(&mut written).with_line_width(40);
+        writer
+            .write_record("seq1", Some("first record"), &long)
+            .unwrap();
+        writer.write_record("seq2", None, b"ACGT").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let text = String::from_utf8(written.clone()).unwrap();
+        assert!(text
+            .lines()
+            .all(|line| line.len() <= 40 || line.starts_with('>')));
+
+        let records = FastaReader::from_reader(written.as_slice())
+            .unwrap()
+            .all_records()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id(), "seq1");
+        assert_eq!(records[0].desc(), Some("first record"));
+        assert_eq!(records[0].seq(), long.as_slice());
+        assert_eq!(records[1].id(), "seq2");
+        assert_eq!(records[1].desc(), None);
+        assert_eq!(records[1].seq(), b"ACGT");
+    }
+
+    #[test]
+    fn fetch_region_of_indexed_file() {
+        let path = std::env::temp_dir().join(format!("dna-indexed-{}.fasta", std::process::id()));
+        let fai_path = path.with_extension("fasta.fai");
+        fs::write(
+            &path,
+            ">seq1 first\nACGTACGTAC\nGT\n>seq2\nGATTACACCG\nTAGGCTAACG\nTTAG\n",
+        )
+        .unwrap();
+
+        let mut reader = IndexedFastaReader::from_file(&path).unwrap();
+        let region = reader.fetch("seq2", 5, 15).unwrap();
+        let whole = reader.fetch("seq1", 0, 100).unwrap();
+        let unknown = reader.fetch("seq3", 0, 10);
+        let fai = fs::read_to_string(&fai_path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&fai_path).unwrap();
+
+        assert_eq!(region, b"CACCGTAGGC");
+        assert_eq!(whole, b"ACGTACGTACGT");
+        assert!(matches!(unknown, Err(FastaReaderError::Io(_))));
+        assert_eq!(fai, "seq1\t12\t12\t10\t11\nseq2\t24\t32\t10\t11\n");
+    }
+}
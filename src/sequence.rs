@@ -0,0 +1,517 @@
+//! Utilities working on a single sequence, usually applied before alignment
+
+use std::{borrow::Cow, fmt::Display};
+
+use bio::alphabets;
+use sha2::{Digest, Sha256};
+use tabled::{Table, Tabled};
+
+use crate::aliner::reverse_complement;
+
+/// Offset of Phred+33 encoded quality strings, as in FASTQ
+pub const PHRED_OFFSET: u8 = 33;
+
+/// Alphabet a sequence is expected to be written in, case insensitive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// `A`, `C`, `G` and `T`
+    Dna,
+    /// `A`, `C`, `G` and `U`
+    Rna,
+    /// The 20 standard amino acids
+    Protein,
+    /// DNA with the IUPAC ambiguity codes, e.g. `N` or `R`
+    IupacDna,
+}
+
+/// Bases a nucleotide or [IUPAC ambiguity code](https://www.bioinformatics.org/sms/iupac.html)
+/// stands for, case insensitive. `U` is read as `T`, `None` for anything else.
+pub fn iupac_expansion(code: u8) -> Option<&'static [u8]> {
+    Some(match code.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => return None,
+    })
+}
+
+impl Alphabet {
+    fn symbols(&self) -> alphabets::Alphabet {
+        match self {
+            Alphabet::Dna => alphabets::dna::alphabet(),
+            Alphabet::Rna => alphabets::rna::alphabet(),
+            Alphabet::Protein => alphabets::protein::alphabet(),
+            Alphabet::IupacDna => alphabets::dna::iupac_alphabet(),
+        }
+    }
+
+    /// `(position, byte)` of every byte of `seq` outside of the alphabet
+    pub fn invalid_symbols(&self, seq: &[u8]) -> Vec<(usize, u8)> {
+        let symbols = self.symbols();
+        seq.iter()
+            .enumerate()
+            .filter(|(_, byte)| !symbols.symbols.contains(**byte as usize))
+            .map(|(pos, byte)| (pos, *byte))
+            .collect()
+    }
+}
+
+/// Base composition of a sequence, see [sequence_stats]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceStats {
+    a: usize,
+    c: usize,
+    g: usize,
+    t: usize,
+    n: usize,
+    other: usize,
+}
+
+impl Tabled for SequenceStats {
+    const LENGTH: usize = 8;
+
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            self.a.to_string().into(),
+            self.c.to_string().into(),
+            self.g.to_string().into(),
+            self.t.to_string().into(),
+            self.n.to_string().into(),
+            self.other.to_string().into(),
+            self.len().to_string().into(),
+            format!("{:.2}", self.gc_content()).into(),
+        ]
+    }
+
+    fn headers() -> Vec<Cow<'static, str>> {
+        ["A", "C", "G", "T", "N", "other", "length", "gc_content"]
+            .map(Cow::Borrowed)
+            .to_vec()
+    }
+}
+
+impl Display for SequenceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Table::new(vec![self]))
+    }
+}
+
+impl SequenceStats {
+    pub fn a(&self) -> usize {
+        self.a
+    }
+
+    pub fn c(&self) -> usize {
+        self.c
+    }
+
+    pub fn g(&self) -> usize {
+        self.g
+    }
+
+    pub fn t(&self) -> usize {
+        self.t
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Bases other than `A`, `C`, `G`, `T` and `N`
+    pub fn other(&self) -> usize {
+        self.other
+    }
+
+    /// Length of the sequence
+    pub fn len(&self) -> usize {
+        self.a + self.c + self.g + self.t + self.n + self.other
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fraction (`0.0..=1.0`) of `G` and `C` among the `A`, `C`, `G` and `T` bases, `0.0` if
+    /// there is none
+    pub fn gc_content(&self) -> f64 {
+        let unambiguous = self.a + self.c + self.g + self.t;
+        if unambiguous == 0 {
+            0.0
+        } else {
+            (self.g + self.c) as f64 / unambiguous as f64
+        }
+    }
+}
+
+/// Count the bases of `seq`, case insensitively
+pub fn sequence_stats(seq: &[u8]) -> SequenceStats {
+    seq.iter()
+        .fold(SequenceStats::default(), |mut stats, base| {
+            match base.to_ascii_uppercase() {
+                b'A' => stats.a += 1,
+                b'C' => stats.c += 1,
+                b'G' => stats.g += 1,
+                b'T' => stats.t += 1,
+                b'N' => stats.n += 1,
+                _ => stats.other += 1,
+            }
+            stats
+        })
+}
+
+/// Collapse runs of the same base (homopolymers) into one base, e.g. `AAAACG` into `ACG`.
+///
+/// Nanopore reads often get homopolymer lengths wrong, aligning collapsed sequences ignores
+/// such errors. Returns the collapsed sequence along with the length of each run, which
+/// [expand_position] uses to map positions back.
+pub fn collapse_homopolymers(seq: &[u8]) -> (Vec<u8>, Vec<usize>) {
+    let mut collapsed = Vec::new();
+    let mut run_lengths: Vec<usize> = Vec::new();
+    for &base in seq {
+        match (collapsed.last(), run_lengths.last_mut()) {
+            (Some(&last), Some(run)) if last == base => *run += 1,
+            _ => {
+                collapsed.push(base);
+                run_lengths.push(1);
+            }
+        }
+    }
+    (collapsed, run_lengths)
+}
+
+/// Map a position of a homopolymer collapsed sequence back to the original sequence, i.e.
+/// the position of the first base of that run. `run_lengths` comes from
+/// [collapse_homopolymers], a position past the end maps to the original length.
+pub fn expand_position(run_lengths: &[usize], pos: usize) -> usize {
+    run_lengths.iter().take(pos).sum()
+}
+
+/// Number of mismatching bases of two equally long slices
+fn mismatches(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(a, b)| a != b).count()
+}
+
+/// Trim a sequencing adapter, and everything after it, from the 3' end of `read`.
+///
+/// The adapter is searched with an ungapped overlap alignment: either fully within the read
+/// or, at the very end, as a partial adapter of at least `min_overlap` bases. At most
+/// `max_mismatch` mismatches are allowed and the leftmost hit wins. Returns the read
+/// unchanged if no adapter is found.
+pub fn strip_adapter(
+    read: &[u8],
+    adapter: &[u8],
+    min_overlap: usize,
+    max_mismatch: usize,
+) -> Vec<u8> {
+    let min_overlap = min_overlap.max(1);
+    let start = (0..read.len()).find(|&start| {
+        let overlap = adapter.len().min(read.len() - start);
+        overlap >= min_overlap
+            && mismatches(&read[start..start + overlap], &adapter[..overlap]) <= max_mismatch
+    });
+    read[..start.unwrap_or(read.len())].to_vec()
+}
+
+/// Best overlap of the end of `r1` with the start of `r2` (already reverse complemented):
+/// the one with the most matches minus mismatches, the longer one on a tie, so a short
+/// overlap matching by chance doesn't beat the true one. Returns its length.
+fn best_overlap(r1: &[u8], r2: &[u8], min_overlap: usize, max_mismatch: usize) -> Option<usize> {
+    (min_overlap.max(1)..=r1.len().min(r2.len()))
+        .filter_map(|overlap| {
+            let mismatches = mismatches(&r1[r1.len() - overlap..], &r2[..overlap]);
+            (mismatches <= max_mismatch).then_some((overlap, mismatches))
+        })
+        .max_by_key(|&(overlap, mismatches)| (overlap as isize - 2 * mismatches as isize, overlap))
+        .map(|(overlap, _)| overlap)
+}
+
+/// Merge paired-end reads, `r1` and `r2` as sequenced, into the fragment they come from,
+/// using the overlap of the 3' end of `r1` with the reverse complement of `r2`.
+///
+/// The overlap has to be at least `min_overlap` bases long with at most `max_mismatch`
+/// mismatches, disagreeing bases are taken from `r1`. Returns `None` when the reads don't
+/// overlap. See [merge_pairs_with_quality] to resolve disagreements by quality.
+pub fn merge_pairs(
+    r1: &[u8],
+    r2: &[u8],
+    min_overlap: usize,
+    max_mismatch: usize,
+) -> Option<Vec<u8>> {
+    let r2 = reverse_complement(r2);
+    let overlap = best_overlap(r1, &r2, min_overlap, max_mismatch)?;
+    Some([r1, &r2[overlap..]].concat())
+}
+
+/// Like [merge_pairs] for `(sequence, quality)` reads with Phred+33 qualities, where
+/// disagreeing bases of the overlap are taken from the read with the higher quality
+pub fn merge_pairs_with_quality(
+    (r1, q1): (&[u8], &[u8]),
+    (r2, q2): (&[u8], &[u8]),
+    min_overlap: usize,
+    max_mismatch: usize,
+) -> Option<Vec<u8>> {
+    let r2 = reverse_complement(r2);
+    let q2 = q2.iter().rev().copied().collect::<Vec<_>>();
+    let overlap = best_overlap(r1, &r2, min_overlap, max_mismatch)?;
+    let start = r1.len() - overlap;
+    let mut merged = [r1, &r2[overlap..]].concat();
+    for i in 0..overlap {
+        let (q1, q2) = (q1.get(start + i), q2.get(i));
+        if r1[start + i] != r2[i] && q2 > q1 {
+            merged[start + i] = r2[i];
+        }
+    }
+    Some(merged)
+}
+
+/// Stable hash of a sequence for deduplication and provenance: the hex encoded SHA-256 of the
+/// uppercased sequence, so it does not depend on case (soft masking)
+pub fn sequence_hash(seq: &[u8]) -> String {
+    Sha256::digest(seq.to_ascii_uppercase())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Trim the bases failing `keep` from both ends of `seq`, as `(removed_start, removed_end,
+/// trimmed)`. A sequence without any kept base is removed from the start.
+fn trim_ends(seq: &[u8], keep: impl Fn(usize) -> bool) -> (usize, usize, &[u8]) {
+    let Some(start) = (0..seq.len()).find(|&pos| keep(pos)) else {
+        return (seq.len(), 0, &seq[seq.len()..]);
+    };
+    let end = (start..seq.len())
+        .rfind(|&pos| keep(pos))
+        .map_or(seq.len(), |pos| pos + 1);
+    (start, seq.len() - end, &seq[start..end])
+}
+
+/// Trim ambiguous `N` bases (case insensitive) from both ends of `seq`, returning the number of
+/// bases removed from the start and the end along with the trimmed sequence, so positions can
+/// be mapped back by adding the first offset
+pub fn trim_ns(seq: &[u8]) -> (usize, usize, &[u8]) {
+    trim_ends(seq, |pos| !seq[pos].eq_ignore_ascii_case(&b'N'))
+}
+
+/// Trim bases with a Phred quality below `min_q` from both ends of a FASTQ read, like
+/// [trim_ns]. `qual` holds the Phred+33 encoded qualities of `seq`, bases without a quality
+/// are trimmed.
+pub fn trim_quality<'s>(seq: &'s [u8], qual: &[u8], min_q: u8) -> (usize, usize, &'s [u8]) {
+    trim_ends(seq, |pos| {
+        qual.get(pos)
+            .is_some_and(|q| q.saturating_sub(PHRED_OFFSET) >= min_q)
+    })
+}
+
+/// Steps of [normalize_sequence], all disabled by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeOpts {
+    /// Uppercase every base, e.g. to drop soft masking
+    pub uppercase: bool,
+    /// Replace `U` by `T`, keeping its case
+    pub rna_to_dna: bool,
+    /// Remove alignment gaps, `-` and `.`
+    pub strip_gaps: bool,
+    /// Remove ASCII whitespace
+    pub strip_whitespace: bool,
+}
+
+impl NormalizeOpts {
+    /// Every step enabled
+    pub fn all() -> Self {
+        Self {
+            uppercase: true,
+            rna_to_dna: true,
+            strip_gaps: true,
+            strip_whitespace: true,
+        }
+    }
+}
+
+/// Clean up a sequence read from a FASTA file before aligning it, see [NormalizeOpts]
+pub fn normalize_sequence(seq: &[u8], opts: NormalizeOpts) -> Vec<u8> {
+    seq.iter()
+        .filter(|base| !(opts.strip_gaps && matches!(base, b'-' | b'.')))
+        .filter(|base| !(opts.strip_whitespace && base.is_ascii_whitespace()))
+        .map(|&base| match base {
+            b'U' if opts.rna_to_dna => b'T',
+            b'u' if opts.rna_to_dna => b't',
+            _ => base,
+        })
+        .map(|base| {
+            if opts.uppercase {
+                base.to_ascii_uppercase()
+            } else {
+                base
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aliner::reverse_complement;
+
+    use super::{
+        collapse_homopolymers, expand_position, merge_pairs, merge_pairs_with_quality,
+        normalize_sequence, sequence_hash, sequence_stats, strip_adapter, trim_ns, trim_quality,
+        Alphabet, NormalizeOpts,
+    };
+
+    #[test]
+    fn collapse_single_run() {
+        assert_eq!(collapse_homopolymers(b"AAAA"), (b"A".to_vec(), vec![4]));
+    }
+
+    #[test]
+    fn collapsed_positions_map_back() {
+        let (collapsed, runs) = collapse_homopolymers(b"GAAAACTTG");
+
+        assert_eq!(collapsed, b"GACTG");
+        assert_eq!(runs, vec![1, 4, 1, 2, 1]);
+        assert_eq!(expand_position(&runs, 0), 0);
+        assert_eq!(expand_position(&runs, 2), 5);
+        assert_eq!(expand_position(&runs, 4), 8);
+        assert_eq!(expand_position(&runs, 5), 9);
+    }
+
+    #[test]
+    fn strip_partial_adapter() {
+        let adapter = b"AGATCGGAAGAGC";
+        // Read ends with the first 9 bases of the adapter, one of them miscalled
+        let read = b"GATTACACCGTAGGCTAGATCGCAA";
+
+        assert_eq!(strip_adapter(read, adapter, 5, 1), b"GATTACACCGTAGGCT");
+        assert_eq!(strip_adapter(read, adapter, 5, 0), read);
+        assert_eq!(strip_adapter(read, adapter, 10, 1), read);
+    }
+
+    #[test]
+    fn merge_pairs_with_20bp_overlap() {
+        let fragment = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGA";
+        // 35 bases each, overlapping on fragment[15..35]
+        let r1 = &fragment[..35];
+        let r2 = reverse_complement(&fragment[15..]);
+
+        assert_eq!(merge_pairs(r1, &r2, 10, 1).as_deref(), Some(&fragment[..]));
+        assert_eq!(merge_pairs(r1, &r2, 25, 1), None);
+    }
+
+    #[test]
+    fn merge_pairs_keeps_higher_quality_base() {
+        let fragment = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGA";
+        let mut r1 = fragment[..35].to_vec();
+        // Miscalled base in the overlap, with a low quality
+        r1[20] = b'G';
+        let mut q1 = vec![b'I'; 35];
+        q1[20] = b'#';
+        let r2 = reverse_complement(&fragment[15..]);
+        let q2 = vec![b'5'; r2.len()];
+
+        assert_eq!(
+            merge_pairs_with_quality((&r1, &q1), (&r2, &q2), 10, 1).as_deref(),
+            Some(&fragment[..])
+        );
+        assert_eq!(
+            merge_pairs(&r1, &r2, 10, 1).map(|merged| merged[20]),
+            Some(b'G')
+        );
+    }
+
+    #[test]
+    fn merge_pairs_prefers_true_overlap_over_chance_one() {
+        let fragment = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGA";
+        let mut r1 = fragment[..35].to_vec();
+        // A mismatch in the 20 bases overlap, while the last 2 bases of r1 match the first 2
+        // of r2 by chance
+        r1[20] = b'G';
+        let r2 = reverse_complement(&fragment[15..]);
+
+        let merged = merge_pairs(&r1, &r2, 2, 1).unwrap();
+        assert_eq!(merged.len(), fragment.len());
+        assert_eq!(merged[..35], r1[..]);
+        assert_eq!(merged[35..], fragment[35..]);
+    }
+
+    #[test]
+    fn sequence_hash_ignores_case() {
+        assert_eq!(sequence_hash(b"ACGTacgt"), sequence_hash(b"acgtACGT"));
+        assert_ne!(sequence_hash(b"ACGTACGT"), sequence_hash(b"ACGTACGA"));
+        assert_eq!(
+            sequence_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn invalid_symbols_of_alphabets() {
+        assert_eq!(Alphabet::Dna.invalid_symbols(b"GATTACAgattaca"), vec![]);
+        assert_eq!(
+            Alphabet::Dna.invalid_symbols(b"GAUN"),
+            vec![(2, b'U'), (3, b'N')]
+        );
+        assert_eq!(Alphabet::Rna.invalid_symbols(b"GAUN"), vec![(3, b'N')]);
+        assert_eq!(
+            Alphabet::IupacDna.invalid_symbols(b"GATN RY"),
+            vec![(4, b' ')]
+        );
+        assert_eq!(Alphabet::Protein.invalid_symbols(b"MKV*"), vec![(3, b'*')]);
+    }
+
+    #[test]
+    fn gc_content_of_sequences() {
+        assert_eq!(sequence_stats(b"GGCC").gc_content(), 1.0);
+        assert_eq!(sequence_stats(b"ATAT").gc_content(), 0.0);
+        assert_eq!(sequence_stats(b"").gc_content(), 0.0);
+
+        let stats = sequence_stats(b"gattACANNc-");
+        assert_eq!((stats.a(), stats.c(), stats.g(), stats.t()), (3, 2, 1, 2));
+        assert_eq!((stats.n(), stats.other(), stats.len()), (2, 1, 11));
+        assert_eq!(stats.gc_content(), 3.0 / 8.0);
+    }
+
+    #[test]
+    fn normalize_with_all_options() {
+        assert_eq!(
+            normalize_sequence(b"acg u-n ", NormalizeOpts::all()),
+            b"ACGTN"
+        );
+        let rna_only = NormalizeOpts {
+            rna_to_dna: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize_sequence(b"acg u-n ", rna_only), b"acg t-n ");
+    }
+
+    #[test]
+    fn trim_ambiguous_ends() {
+        assert_eq!(trim_ns(b"NNACGTNN"), (2, 2, b"ACGT".as_slice()));
+        assert_eq!(trim_ns(b"nACNGT"), (1, 0, b"ACNGT".as_slice()));
+        assert_eq!(trim_ns(b"NNN"), (3, 0, b"".as_slice()));
+    }
+
+    #[test]
+    fn trim_low_quality_ends() {
+        // Phred 2, five times 30, then 10
+        let qual = b"#?????+";
+        assert_eq!(
+            trim_quality(b"ACGTACG", qual, 20),
+            (1, 1, b"CGTAC".as_slice())
+        );
+        assert_eq!(
+            trim_quality(b"ACGTACG", qual, 0),
+            (0, 0, b"ACGTACG".as_slice())
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Statistical significance of local alignment scores, following
+//! [Karlin-Altschul statistics](https://www.ncbi.nlm.nih.gov/BLAST/tutorial/Altschul-1.html)
+
+use std::fmt::Display;
+
+use bio::alignment::pairwise::MatchFunc;
+
+use crate::{
+    aliner::{DiffStat, GapPanelty},
+    bench_support::random_dna,
+};
+
+/// Euler–Mascheroni constant
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+/// Expected number of hits with a score of at least `score` by chance alone, when searching a
+/// query of `query_len` against a database of `db_len` bases: `E = K * m * n * e^(-lambda * S)`
+pub fn evalue(score: i32, query_len: usize, db_len: usize, lambda: f64, k: f64) -> f64 {
+    k * query_len as f64 * db_len as f64 * (-lambda * score as f64).exp()
+}
+
+/// Karlin-Altschul parameters of a scoring scheme
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KarlinAltschul {
+    pub lambda: f64,
+    pub k: f64,
+}
+
+impl KarlinAltschul {
+    /// See [evalue]
+    pub fn evalue(&self, score: i32, query_len: usize, db_len: usize) -> f64 {
+        evalue(score, query_len, db_len, self.lambda, self.k)
+    }
+}
+
+/// Estimate `lambda` and `K` for a scoring scheme by locally aligning `samples` pairs of
+/// random sequences of `seq_len` bases, and fitting the extreme value (Gumbel) distribution
+/// of their scores by the method of moments. The same `seed` gives the same estimate.
+///
+/// Gapped scoring has no closed form, so this is the practical way to get them. More
+/// samples and longer sequences give better estimates, at the cost of `samples * seq_len^2`.
+///
+/// `None` when the scores can't be fitted: fewer than 2 samples, or every sample scoring the
+/// same (e.g. empty sequences).
+pub fn estimate_karlin_altschul<F>(
+    gap_penalty: &GapPanelty,
+    score: &F,
+    seq_len: usize,
+    samples: usize,
+    seed: u64,
+) -> Option<KarlinAltschul>
+where
+    F: MatchFunc + Clone + Display,
+{
+    tracing::info!(
+        "Estimating Karlin-Altschul parameters from {} random alignments using {} and {}",
+        samples,
+        gap_penalty,
+        score
+    );
+    if samples < 2 {
+        return None;
+    }
+    let scores = (0..samples)
+        .map(|sample| {
+            let pair = random_dna(2 * seq_len, seed.wrapping_add(sample as u64));
+            let (a, b) = pair.split_at(seq_len);
+            let mut diff =
+                DiffStat::new(a, b, (gap_penalty.open, gap_penalty.extend), score.clone());
+            diff.pairwise_aligner_local();
+            diff.alignment()
+                .map_or(0.0, |alignment| alignment.score as f64)
+        })
+        .collect::<Vec<_>>();
+
+    let mean = scores.iter().sum::<f64>() / samples as f64;
+    let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / samples as f64;
+    if variance == 0.0 {
+        return None;
+    }
+    let lambda = std::f64::consts::PI / (variance.sqrt() * 6f64.sqrt());
+    let mode = mean - EULER_GAMMA / lambda;
+    let k = (lambda * mode).exp() / (seq_len as f64 * seq_len as f64);
+    Some(KarlinAltschul { lambda, k })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{estimate_karlin_altschul, evalue};
+    use crate::aliner::{GapPanelty, Score};
+
+    #[test]
+    fn evalue_formula() {
+        // 0.1 * 100 * 1000 * e^(-0.3 * 30) = 10^4 * e^-9
+        let expected = 1.234_098_040_866_795_6;
+        assert!((evalue(30, 100, 1000, 0.3, 0.1) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimated_parameters_are_usable() {
+        let gap = GapPanelty::new(-5, -2);
+        let score = Score::new(1, -3);
+        let params = estimate_karlin_altschul(&gap, &score, 100, 100, 7).unwrap();
+
+        assert_eq!(
+            Some(params),
+            estimate_karlin_altschul(&gap, &score, 100, 100, 7)
+        );
+        assert!(params.lambda.is_finite() && params.lambda > 0.0);
+        assert!(params.k.is_finite() && params.k > 0.0);
+        // Long perfect hit is significant, a tiny one is not
+        assert!(params.evalue(50, 100, 10_000) < 1e-3);
+        assert!(params.evalue(3, 100, 10_000) > 1.0);
+    }
+
+    #[test]
+    fn degenerate_samples_are_not_fitted() {
+        let gap = GapPanelty::new(-5, -2);
+        let score = Score::new(1, -3);
+
+        assert_eq!(estimate_karlin_altschul(&gap, &score, 100, 0, 7), None);
+        assert_eq!(estimate_karlin_altschul(&gap, &score, 100, 1, 7), None);
+        // Empty sequences all score 0
+        assert_eq!(estimate_karlin_altschul(&gap, &score, 0, 10, 7), None);
+    }
+}
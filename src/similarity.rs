@@ -0,0 +1,138 @@
+//! Alignment free similarity measures, useful for a quick comparison of sequences
+//! that are too divergent (or too many) to align.
+
+use std::{collections::HashMap, io::Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// Size of `data` once gzip compressed
+fn compressed_len(data: &[u8]) -> usize {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(data)
+        .expect("Writing to an in-memory gzip encoder cant fail");
+    encoder
+        .finish()
+        .expect("Writing to an in-memory gzip encoder cant fail")
+        .len()
+}
+
+/// Calculate [Normalized Compression Distance](https://en.wikipedia.org/wiki/Normalized_compression_distance)
+/// using gzip as compressor: `NCD = (C(ab) - min(C(a),C(b))) / max(C(a),C(b))`
+///
+/// Close to `0.0` for similar sequences and close to `1.0` for unrelated ones. Compressor
+/// overhead makes the value noisy for very short sequences.
+pub fn ncd(a: &[u8], b: &[u8]) -> f64 {
+    tracing::info!("Calculating normalized compression distance");
+    let c_a = compressed_len(a);
+    let c_b = compressed_len(b);
+    let c_ab = compressed_len(&[a, b].concat());
+    (c_ab as f64 - c_a.min(c_b) as f64) / c_a.max(c_b) as f64
+}
+
+/// Number of occurrences of every (overlapping) k-mer of `seq`, uppercased so counts don't
+/// depend on case. Empty if `seq` is shorter than `k` or `k` is 0.
+pub fn kmer_counts(seq: &[u8], k: usize) -> HashMap<Vec<u8>, u32> {
+    let mut counts = HashMap::new();
+    if k == 0 {
+        return counts;
+    }
+    for kmer in seq.windows(k) {
+        *counts.entry(kmer.to_ascii_uppercase()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Cosine distance (`1 - cosine similarity`, `0.0..=1.0`) between the k-mer spectra of two
+/// sequences, see [kmer_counts]. Unlike a Jaccard distance it weights k-mers by their counts,
+/// so repeats matter.
+///
+/// `0.0` for identical spectra, and `1.0` when they share no k-mer or one of them is empty.
+pub fn kmer_distance(a: &[u8], b: &[u8], k: usize) -> f64 {
+    tracing::info!("Calculating {}-mer cosine distance", k);
+    let (a, b) = (kmer_counts(a, k), kmer_counts(b, k));
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    let norm = |counts: &HashMap<Vec<u8>, u32>| {
+        counts
+            .values()
+            .map(|count| (*count as f64).powi(2))
+            .sum::<f64>()
+    };
+    let (norm_a, norm_b) = (norm(&a), norm(&b));
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    let dot = a
+        .iter()
+        .filter_map(|(kmer, count)| Some(*count as f64 * *b.get(kmer)? as f64))
+        .sum::<f64>();
+    (1.0 - dot / (norm_a * norm_b).sqrt()).clamp(0.0, 1.0)
+}
+
+/// Distance between two sequences, so callers can be generic over the metric
+pub trait DistanceMetric {
+    fn distance(&self, a: &[u8], b: &[u8]) -> u64;
+}
+
+/// [Hamming](https://en.wikipedia.org/wiki/Hamming_distance) distance, panics if the sequences
+/// have different lengths
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hamming;
+
+impl DistanceMetric for Hamming {
+    fn distance(&self, a: &[u8], b: &[u8]) -> u64 {
+        bio::alignment::distance::simd::hamming(a, b)
+    }
+}
+
+/// [Levenshtein](https://en.wikipedia.org/wiki/Levenshtein_distance) (edit) distance
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Levenshtein;
+
+impl DistanceMetric for Levenshtein {
+    fn distance(&self, a: &[u8], b: &[u8]) -> u64 {
+        bio::alignment::distance::simd::levenshtein(a, b).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bench_support::random_dna;
+
+    use super::{kmer_counts, kmer_distance, ncd};
+
+    #[test]
+    fn ncd_identical_sequences() {
+        let seq = random_dna(5000, 7);
+        assert!(ncd(&seq, &seq) < 0.1);
+    }
+
+    #[test]
+    fn ncd_unrelated_sequences() {
+        let a = random_dna(5000, 7);
+        let b = random_dna(5000, 42);
+        assert!(ncd(&a, &b) > 0.9);
+    }
+
+    #[test]
+    fn kmer_counts_of_homopolymer() {
+        assert_eq!(
+            kmer_counts(b"AAAA", 2).into_iter().collect::<Vec<_>>(),
+            vec![(b"AA".to_vec(), 3)]
+        );
+        assert_eq!(kmer_counts(b"acGT", 2).get(b"AC".as_slice()), Some(&1));
+        assert!(kmer_counts(b"A", 2).is_empty());
+    }
+
+    #[test]
+    fn kmer_distance_of_spectra() {
+        let a = random_dna(5000, 7);
+        let b = random_dna(5000, 42);
+
+        assert_eq!(kmer_distance(&a, &a, 8), 0.0);
+        assert!(kmer_distance(&a, &b, 8) > 0.9);
+        assert_eq!(kmer_distance(b"ACGT", b"", 2), 1.0);
+    }
+}
@@ -0,0 +1,248 @@
+//! Working with the protein coding potential of DNA sequences
+
+use crate::aliner::reverse_complement;
+
+/// Genetic code (codon table) used for translation, see the
+/// [NCBI translation tables](https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GeneticCode {
+    /// Table 1
+    #[default]
+    Standard,
+    /// Table 2
+    VertebrateMito,
+    /// Table 3
+    YeastMito,
+    /// Table 5
+    InvertebrateMito,
+    /// Table 11, bacterial, archaeal and plant plastid code
+    BacterialPlastid,
+}
+
+impl GeneticCode {
+    /// NCBI translation table number
+    pub fn ncbi_id(&self) -> u8 {
+        match self {
+            GeneticCode::Standard => 1,
+            GeneticCode::VertebrateMito => 2,
+            GeneticCode::YeastMito => 3,
+            GeneticCode::InvertebrateMito => 5,
+            GeneticCode::BacterialPlastid => 11,
+        }
+    }
+
+    /// Amino acids and start codons (`M`) of all 64 codons, in NCBI's `TCAG` order
+    fn table(&self) -> (&'static [u8; 64], &'static [u8; 64]) {
+        match self {
+            GeneticCode::Standard => (
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                b"---M------**--*----M---------------M----------------------------",
+            ),
+            GeneticCode::VertebrateMito => (
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+                b"----------**--------------------MMMM----------**---M------------",
+            ),
+            GeneticCode::YeastMito => (
+                b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                b"----------**----------------------MM---------------M------------",
+            ),
+            GeneticCode::InvertebrateMito => (
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+                b"---M------**--------------------MMMM---------------M------------",
+            ),
+            GeneticCode::BacterialPlastid => (
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                b"---M------**--*----M------------MMMM---------------M------------",
+            ),
+        }
+    }
+
+    /// Index of a codon in the tables, `None` for anything but 3 of `ACGTU` (case
+    /// insensitive)
+    fn codon_index(codon: &[u8]) -> Option<usize> {
+        if codon.len() != 3 {
+            return None;
+        }
+        codon.iter().try_fold(0, |idx, base| {
+            let base = match base.to_ascii_uppercase() {
+                b'T' | b'U' => 0,
+                b'C' => 1,
+                b'A' => 2,
+                b'G' => 3,
+                _ => return None,
+            };
+            Some(idx * 4 + base)
+        })
+    }
+
+    /// One letter amino acid of a codon, `*` for a stop and `X` for an unknown codon
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        Self::codon_index(codon).map_or(b'X', |idx| self.table().0[idx])
+    }
+
+    pub fn is_stop(&self, codon: &[u8]) -> bool {
+        self.translate_codon(codon) == b'*'
+    }
+
+    /// Whether the codon can initiate translation
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        Self::codon_index(codon).is_some_and(|idx| self.table().1[idx] == b'M')
+    }
+}
+
+/// Stop codon of the standard genetic code, case insensitive
+fn is_stop_codon(codon: &[u8]) -> bool {
+    GeneticCode::Standard.is_stop(codon)
+}
+
+/// Translate a frame of a DNA (or RNA) sequence into a protein using `code`, one letter per
+/// amino acid with `*` for stops and `X` for unknown codons. Incomplete trailing codons are
+/// dropped.
+///
+/// Frames `0..=2` start at that offset of the sequence, frames `3..=5` at offset `frame - 3`
+/// of its reverse complement, as in [best_coding_frame].
+pub fn translate_with(seq: &[u8], frame: usize, code: GeneticCode) -> Vec<u8> {
+    tracing::info!(
+        "Translating frame {} with genetic code {}",
+        frame,
+        code.ncbi_id()
+    );
+    let reverse;
+    let (strand, offset) = if frame < 3 {
+        (seq, frame)
+    } else {
+        reverse = reverse_complement(seq);
+        (reverse.as_slice(), frame - 3)
+    };
+    strand
+        .get(offset..)
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .map(|codon| code.translate_codon(codon))
+        .collect()
+}
+
+/// Translate a frame of a DNA (or RNA) sequence using the standard genetic code, see
+/// [translate_with]
+pub fn translate(dna: &[u8], frame: usize) -> Vec<u8> {
+    translate_with(dna, frame, GeneticCode::Standard)
+}
+
+/// Fraction (`0.0..=1.0`) of the codons of a frame covered by its longest stretch without a
+/// stop codon
+fn longest_orf_fraction(seq: &[u8], offset: usize) -> f64 {
+    let codons = seq.get(offset..).unwrap_or_default().chunks_exact(3);
+    let total = codons.len();
+    let (longest, _) = codons.fold((0, 0), |(longest, current), codon| {
+        if is_stop_codon(codon) {
+            (longest, 0)
+        } else {
+            (longest.max(current + 1), current + 1)
+        }
+    });
+    if total == 0 {
+        0.0
+    } else {
+        longest as f64 / total as f64
+    }
+}
+
+/// Find the most likely coding frame of a sequence among all six frames, scored by the
+/// fraction of the frame's codons covered by its longest open reading frame (stretch of
+/// codons without a stop). Coding frames rarely hit a stop, while in other frames stops are
+/// frequent.
+///
+/// Frames `0..=2` start at that offset of the sequence, frames `3..=5` at offset `frame - 3`
+/// of its reverse complement. Returns the best frame along with its score, the lowest frame
+/// wins a tie.
+pub fn best_coding_frame(seq: &[u8]) -> (usize, f64) {
+    tracing::info!("Finding best coding frame");
+    let reverse = reverse_complement(seq);
+    (0..6)
+        .map(|frame| {
+            let score = if frame < 3 {
+                longest_orf_fraction(seq, frame)
+            } else {
+                longest_orf_fraction(&reverse, frame - 3)
+            };
+            (frame, score)
+        })
+        .fold((0, f64::MIN), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{best_coding_frame, translate, translate_with, GeneticCode};
+    use crate::{
+        aliner::{reverse_complement, DiffStat, MatrixScore},
+        sequence::Alphabet,
+    };
+
+    /// ORF of 41 codons from offset 1, other frames hit stops frequently
+    const ORF: &[u8] = b"CATGGCCATCTGCGTCCATCGCATTAACCCAGTAGGTACTGCCTTAGTTGCACTCCTAACTCATGTTAACGGACTTACGGGCACTAGCTTCTTACTGCCCTCTCTGTTTCTCTTAAGGGACGTCTAAG";
+
+    #[test]
+    fn frame_with_long_orf_is_chosen() {
+        assert_eq!(best_coding_frame(ORF), (1, 41.0 / 42.0));
+    }
+
+    #[test]
+    fn frame_of_reverse_strand() {
+        assert_eq!(best_coding_frame(&reverse_complement(ORF)).0, 4);
+    }
+
+    #[test]
+    fn translate_with_mito_code() {
+        assert_eq!(translate_with(b"ATGTGA", 0, GeneticCode::Standard), b"M*");
+        assert_eq!(
+            translate_with(b"ATGTGA", 0, GeneticCode::VertebrateMito),
+            b"MW"
+        );
+        assert_eq!(translate_with(b"AGATAGN", 0, GeneticCode::Standard), b"R*");
+        assert_eq!(
+            translate_with(b"AGATAG", 0, GeneticCode::VertebrateMito),
+            b"**"
+        );
+        assert_eq!(translate_with(b"TCACAT", 3, GeneticCode::Standard), b"M*");
+
+        assert!(GeneticCode::VertebrateMito.is_start(b"ATA"));
+        assert!(!GeneticCode::Standard.is_start(b"ATA"));
+        // Unlike the mitochondrial codes, TGA is still a stop in the plastid code
+        assert_eq!(
+            translate_with(b"TGA", 0, GeneticCode::BacterialPlastid),
+            b"*"
+        );
+        assert!(GeneticCode::BacterialPlastid.is_start(b"GTG"));
+    }
+
+    #[test]
+    fn translate_standard_code() {
+        assert_eq!(translate(b"ATGAAATAA", 0), b"MK*");
+        assert_eq!(translate(b"ATGANATAAGC", 0), b"MX*");
+        assert_eq!(translate(b"CATGAAATAA", 1), b"MK*");
+    }
+
+    #[test]
+    fn align_translations() {
+        let reference = translate(b"ATGAAAGTTTGG", 0);
+        let query = translate(b"ATGAGAGTTTGG", 0);
+        assert_eq!(query, b"MRVW");
+
+        let mut diffstat = DiffStat::new(
+            &reference[..],
+            &query[..],
+            (-11, -1),
+            MatrixScore::blosum62(),
+        );
+        assert_eq!(diffstat.validate_alphabet(Alphabet::Protein), Ok(()));
+        diffstat.pairwise_aligner_global();
+        // M/M 5, K/R 2, V/V 4 and W/W 11
+        assert_eq!(diffstat.alignment_score(), Some(22));
+    }
+}
@@ -0,0 +1,135 @@
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_dna_sequence_analysis");
+const DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+
+#[cfg(feature = "serde")]
+#[test]
+fn format_json() {
+    let output = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/reference.fasta")])
+        .args(["--query", &format!("{DATA}/query.fasta")])
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let report = &json[0];
+    assert_eq!(report["query"], "query1");
+    assert_eq!(report["reference"], "ref1");
+    assert!(report["match"].as_u64().unwrap() > 0);
+    assert!(report["insertions"].is_u64());
+    assert!(report["deletions"].is_u64());
+}
+
+#[test]
+fn verify_pair_warns_on_same_file() {
+    let reference = format!("{DATA}/reference.fasta");
+    let output = Command::new(BIN)
+        .args(["--reference", &reference, "--query", &reference])
+        .arg("--verify-pair")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("reference ref1: 33 bp"));
+    assert!(stderr.contains("WARN"));
+    assert!(stderr.contains("Reference and query have the same ID `ref1`"));
+}
+
+#[test]
+fn mode_global_with_scoring() {
+    let output = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/reference.fasta")])
+        .args(["--query", &format!("{DATA}/query.fasta")])
+        .args(["--mode", "global", "--match", "2", "--mismatch", "-3"])
+        .args(["--gap-open", "-4", "--gap-extend", "-2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let rejected = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/reference.fasta")])
+        .args(["--query", &format!("{DATA}/query.fasta")])
+        .args(["--gap-open", "3"])
+        .output()
+        .unwrap();
+    assert!(!rejected.status.success());
+}
+
+#[test]
+fn batch_reports_every_query() {
+    let output = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/references.fasta")])
+        .args(["--query", &format!("{DATA}/queries.fasta")])
+        .args(["--batch", "--format", "csv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Each query against the reference it was derived from
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let assigned = stdout
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let fields = line.split(',').collect::<Vec<_>>();
+            (fields[0], fields[1])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        assigned,
+        [("query1", "ref1"), ("query2", "ref2"), ("query3", "ref1")]
+    );
+
+    // Table only options, which batch mode doesn't apply
+    for flags in [&["--sv-only"][..], &["--max-diffs", "2"]] {
+        let rejected = Command::new(BIN)
+            .args(["--reference", &format!("{DATA}/references.fasta")])
+            .args(["--query", &format!("{DATA}/queries.fasta")])
+            .arg("--batch")
+            .args(flags)
+            .output()
+            .unwrap();
+        assert!(!rejected.status.success());
+    }
+}
+
+#[test]
+fn batch_with_threads_keeps_query_order() {
+    let output = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/references.fasta")])
+        .args(["--query", &format!("{DATA}/queries.fasta")])
+        .args(["--batch", "--threads", "2", "--format", "csv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let queries = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(queries, ["query1", "query2", "query3"]);
+}
+
+#[test]
+fn repeated_query_files() {
+    let output = Command::new(BIN)
+        .args(["--reference", &format!("{DATA}/reference.fasta")])
+        .args(["--query", &format!("{DATA}/query.fasta")])
+        .args(["--query", &format!("{DATA}/reference.fasta")])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows = stdout
+        .lines()
+        .filter(|line| line.starts_with("| query1 ") || line.starts_with("| ref1 "))
+        .count();
+    assert_eq!(rows, 2);
+}